@@ -1,14 +1,121 @@
 use serde::Deserialize;
+use std::path::PathBuf;
+
+use super::{reader::WorkDirConfig, token::Token};
 
 #[derive(Clone, Debug, PartialEq, Deserialize)]
 pub struct SourceRepository {
     pub owner: String,
     pub name: String,
     pub git_ref: String,
+    /// Overrides the top-level `api_url`, `web_url` and credentials
+    #[serde(default, flatten)]
+    pub connection: ConnectionConfig,
 }
 
 #[derive(Clone, Debug, PartialEq, Deserialize)]
 pub struct DestinationRepository {
     pub owner: String,
     pub name: String,
+    /// Base branch of the pull request, defaults to the repository's default branch
+    #[serde(default)]
+    pub git_ref: Option<String>,
+    /// Overrides the top-level `api_url`, `web_url` and credentials
+    #[serde(default, flatten)]
+    pub connection: ConnectionConfig,
+    #[serde(default, flatten)]
+    pub overrides: DestinationOverrides,
+}
+
+/// Settings of a single destination taking over the top-level ones, e.g. for
+/// a destination keeping the shared code in another folder:
+///
+/// ```yaml
+/// destination_files: glob("vendor/shared/**")
+/// transformations:
+///   - fn: builtin.move
+///     args:
+///       before: ""
+///       after: vendor/shared
+/// pull_request:
+///   labels: [vendored]
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+pub struct DestinationOverrides {
+    #[serde(default)]
+    pub destination_files: Option<WorkDirConfig>,
+    /// Replaces the top-level transformations, an empty list syncs the files unchanged
+    #[serde(default)]
+    pub transformations: Option<Vec<serde_json::Value>>,
+    /// Overrides single settings of the top-level `pull_request`
+    #[serde(default)]
+    pub pull_request: PullRequestConfig,
+}
+
+/// How the pull requests are opened in the destinations.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+pub struct PullRequestConfig {
+    /// Defaults to `Update from <owner>/<name> branch: <git_ref>` of the source
+    #[serde(default)]
+    pub title: Option<String>,
+    /// Opens new pull requests as drafts, defaults to `false`
+    #[serde(default)]
+    pub draft: Option<bool>,
+    /// Labels added to the pull request
+    #[serde(default)]
+    pub labels: Option<Vec<String>>,
+}
+
+impl PullRequestConfig {
+    /// Takes every setting from `overrides` when it is set there.
+    pub fn merge(&self, overrides: &PullRequestConfig) -> PullRequestConfig {
+        PullRequestConfig {
+            title: overrides.title.clone().or_else(|| self.title.clone()),
+            draft: overrides.draft.or(self.draft),
+            labels: overrides.labels.clone().or_else(|| self.labels.clone()),
+        }
+    }
+}
+
+/// Where a repository is hosted and how to authenticate there, e.g. for a
+/// GitHub Enterprise Server:
+///
+/// ```yaml
+/// api_url: https://github.example.com/api/v3
+/// token_command: cat ~/.ghes_token
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+pub struct ConnectionConfig {
+    /// Root of the REST API, defaults to `https://api.github.com`
+    #[serde(default)]
+    pub api_url: Option<String>,
+    /// Root of the web pages linked from pull requests, derived from `api_url` when not set
+    #[serde(default)]
+    pub web_url: Option<String>,
+    #[serde(flatten)]
+    pub credentials: CredentialsConfig,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+pub struct CredentialsConfig {
+    /// Resolved with `token::resolve_token`, which also looks at `--token`,
+    /// the environment, `token_file` and `token_command`
+    #[serde(default)]
+    pub token: Option<Token>,
+    /// Path of a file holding the token, relative to the config file
+    #[serde(default)]
+    pub token_file: Option<String>,
+    /// Shell command printing the token, e.g. `gh auth token`
+    #[serde(default)]
+    pub token_command: Option<String>,
+    /// GitHub App to authenticate as, instead of a token
+    #[serde(default)]
+    pub app: Option<AppConfig>,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct AppConfig {
+    pub id: u64,
+    /// PEM private key of the app, relative to the config file
+    pub private_key_path: PathBuf,
 }
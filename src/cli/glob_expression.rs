@@ -0,0 +1,233 @@
+use crate::error::{Error, Result};
+
+/// Patterns of a `glob(...)` expression, before they are compiled.
+#[derive(Debug, PartialEq)]
+pub struct GlobArgs {
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+}
+
+/// Parses a glob expression. Both include and exclude accept a single pattern
+/// or a list of patterns, the exclude may be named:
+///
+/// ```text
+/// glob("src/**")
+/// glob("src/**", "src/dist/**")
+/// glob(["src/**", "docs/**"], exclude = ["**/*.tmp", "**/*.bak"])
+/// ```
+///
+/// Patterns are double quoted strings, `\"` and `\\` are the only escapes.
+pub fn parse_glob_expression(val: &str) -> Result<GlobArgs> {
+    GlobParser {
+        input: val,
+        position: 0,
+    }
+    .parse()
+}
+
+struct GlobParser<'a> {
+    input: &'a str,
+    position: usize,
+}
+
+impl<'a> GlobParser<'a> {
+    fn parse(mut self) -> Result<GlobArgs> {
+        self.skip_whitespace();
+        self.expect("glob(")?;
+        self.skip_whitespace();
+        let include = self.parse_patterns()?;
+        if include.is_empty() {
+            return Err(self.error("at least one include pattern"));
+        }
+
+        self.skip_whitespace();
+        let mut exclude = Vec::new();
+        if self.eat(",") {
+            self.skip_whitespace();
+            if self.eat("exclude") {
+                self.skip_whitespace();
+                self.expect("=")?;
+                self.skip_whitespace();
+            }
+            exclude = self.parse_patterns()?;
+            self.skip_whitespace();
+        }
+
+        self.expect(")")?;
+        self.skip_whitespace();
+        if self.position < self.input.len() {
+            return Err(self.error("end of expression"));
+        }
+
+        Ok(GlobArgs { include, exclude })
+    }
+
+    fn parse_patterns(&mut self) -> Result<Vec<String>> {
+        if !self.eat("[") {
+            return Ok(vec![self.parse_string()?]);
+        }
+
+        let mut patterns = Vec::new();
+        loop {
+            self.skip_whitespace();
+            if self.eat("]") {
+                return Ok(patterns);
+            }
+
+            patterns.push(self.parse_string()?);
+
+            self.skip_whitespace();
+            if !self.eat(",") {
+                self.expect("]")?;
+                return Ok(patterns);
+            }
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<String> {
+        let start = self.position;
+        self.expect("\"")?;
+
+        let mut value = String::new();
+        let mut chars = self.input[self.position..].char_indices();
+        while let Some((offset, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.position += offset + 1;
+                    return Ok(value);
+                }
+                '\\' => match chars.next() {
+                    Some((_, escaped @ ('"' | '\\'))) => value.push(escaped),
+                    _ => {
+                        self.position += offset;
+                        return Err(self.error("`\\\"` or `\\\\` escape"));
+                    }
+                },
+                c => value.push(c),
+            }
+        }
+
+        self.position = start;
+        Err(self.error("closing `\"` for the pattern"))
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = &self.input[self.position..];
+        self.position += rest.len() - rest.trim_start().len();
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        if self.input[self.position..].starts_with(token) {
+            self.position += token.len();
+            return true;
+        }
+
+        false
+    }
+
+    fn expect(&mut self, token: &str) -> Result<()> {
+        if self.eat(token) {
+            return Ok(());
+        }
+
+        Err(self.error(&format!("`{}`", token)))
+    }
+
+    fn error(&self, expected: &str) -> Error {
+        let column = self.input[..self.position].chars().count() + 1;
+
+        Error::Config(format!(
+            "invalid glob `{}`: expected {} at column {}",
+            self.input, expected, column
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_glob_expression, GlobArgs};
+
+    fn to_strings(patterns: &[&str]) -> Vec<String> {
+        patterns.iter().map(|pattern| pattern.to_string()).collect()
+    }
+
+    #[test]
+    fn test_single_pattern() {
+        assert_eq!(
+            parse_glob_expression(r#"glob("src/**")"#).unwrap(),
+            GlobArgs {
+                include: to_strings(&["src/**"]),
+                exclude: vec![],
+            }
+        );
+        assert_eq!(
+            parse_glob_expression(r#"glob("src/**", "src/dist/**")"#).unwrap(),
+            GlobArgs {
+                include: to_strings(&["src/**"]),
+                exclude: to_strings(&["src/dist/**"]),
+            }
+        );
+    }
+
+    #[test]
+    fn test_pattern_lists() {
+        assert_eq!(
+            parse_glob_expression(
+                r#"glob(["src/**", "docs/{a,b}/**"], exclude = ["**/*.tmp", "say \"hi\"",])"#
+            )
+            .unwrap(),
+            GlobArgs {
+                include: to_strings(&["src/**", "docs/{a,b}/**"]),
+                exclude: to_strings(&["**/*.tmp", "say \"hi\""]),
+            }
+        );
+        assert_eq!(
+            parse_glob_expression(r#"glob( [ "a,b" ] , exclude=[] )"#).unwrap(),
+            GlobArgs {
+                include: to_strings(&["a,b"]),
+                exclude: vec![],
+            }
+        );
+    }
+
+    #[test]
+    fn test_malformed_expressions() {
+        let cases = [
+            (
+                r#"glob("src/**""#,
+                r#"invalid glob `glob("src/**"`: expected `)` at column 14"#,
+            ),
+            (
+                r#"glob("src/**)"#,
+                r#"invalid glob `glob("src/**)`: expected closing `"` for the pattern at column 6"#,
+            ),
+            (
+                r#"glob(["a" "b"])"#,
+                r#"invalid glob `glob(["a" "b"])`: expected `]` at column 11"#,
+            ),
+            (
+                r#"glob([])"#,
+                r#"invalid glob `glob([])`: expected at least one include pattern at column 8"#,
+            ),
+            (
+                r#"glob("a", exclude ["b"])"#,
+                r#"invalid glob `glob("a", exclude ["b"])`: expected `=` at column 19"#,
+            ),
+            (
+                r#"glob("a") + glob("b")"#,
+                r#"invalid glob `glob("a") + glob("b")`: expected end of expression at column 11"#,
+            ),
+            (
+                r#"glob("a\b")"#,
+                r#"invalid glob `glob("a\b")`: expected `\"` or `\\` escape at column 8"#,
+            ),
+        ];
+
+        for (expression, message) in cases {
+            assert_eq!(
+                parse_glob_expression(expression).unwrap_err().to_string(),
+                format!("invalid config: {}", message)
+            );
+        }
+    }
+}
@@ -1,28 +1,97 @@
 pub mod common;
+pub mod glob_expression;
 pub mod parser;
 pub mod reader;
+pub mod token;
 
 pub use common::*;
-pub use parser::{GlobExpression, MoveArgs, ParsedConfig, Transformation, WorkDirExpression};
+pub use parser::{
+    Connection, Credentials, GithubApp, GlobExpression, MoveArgs, ParsedConfig, Transformation,
+    UpdateFn, WorkDirExpression, DEFAULT_MAX_CONCURRENCY,
+};
+pub use token::Token;
 
-use anyhow::{Context, Result};
+use crate::error::{Error, Result};
 use clap::Parser;
-use parser::parse_config;
-use reader::read_config;
+use parser::{parse_config, reverse_transformations};
+use reader::read_jobs;
+use token::resolve_tokens;
 #[derive(Parser)]
 pub struct Args {
     #[clap(short, long, parse(from_os_str))]
-    config: std::path::PathBuf,
+    pub config: std::path::PathBuf,
+
+    /// Print the planned changes and their diff for every destination without writing anything
+    #[clap(long)]
+    pub dry_run: bool,
+
+    /// Runs only the job with this name, can be repeated. Runs every job when not set
+    #[clap(long)]
+    pub job: Vec<String>,
+
+    /// Maximum number of concurrent requests, overrides `max_concurrency` from the config
+    #[clap(long)]
+    pub max_concurrency: Option<usize>,
+
+    /// Sync every destination back to the source by undoing the transformations,
+    /// opening the pull requests against the source repository
+    #[clap(long)]
+    pub reverse: bool,
+
+    /// GitHub token, takes precedence over `SYNCY_TOKEN`, `GITHUB_TOKEN` and the config.
+    /// Ignored when the config authenticates as a GitHub App
+    #[clap(long)]
+    pub token: Option<String>,
+
+    /// Print a unified diff of the planned changes before writing them
+    #[clap(short, long)]
+    pub verbose: bool,
 }
 
-pub fn run() -> Result<ParsedConfig, Box<dyn std::error::Error>> {
+pub fn run() -> Result<(Args, Vec<ParsedConfig>)> {
     let args = Args::parse();
 
-    let result = std::fs::read_to_string(&args.config)
-        .with_context(|| format!("could not read file `{:?}`", &args.config))?;
+    let result = std::fs::read_to_string(&args.config).map_err(|error| {
+        Error::Config(format!(
+            "could not read file `{:?}`: {}",
+            &args.config, error
+        ))
+    })?;
+
+    let mut content = read_jobs(&result)?;
+    content.select(&args.job)?;
+
+    let config_dir = args
+        .config
+        .parent()
+        .unwrap_or_else(|| std::path::Path::new(""));
+    resolve_tokens(
+        &mut content,
+        args.token.as_deref(),
+        |name| std::env::var(name).ok(),
+        config_dir,
+    )?;
+
+    let mut jobs = Vec::new();
+    for (name, mut job_config) in content.into_configs() {
+        if args.max_concurrency.is_some() {
+            job_config.max_concurrency = args.max_concurrency;
+        }
+
+        let mut enhanced_config = parse_config(job_config)?;
+        enhanced_config.job = name;
+        if args.reverse {
+            for destination in enhanced_config.destinations.iter() {
+                reverse_transformations(
+                    &enhanced_config
+                        .for_destination(destination)?
+                        .transformations,
+                )?;
+            }
+        }
 
-    let content = read_config(&result).expect("Can't parse config");
-    let enhanced_config = parse_config(content);
+        jobs.push(enhanced_config);
+    }
 
-    Ok(enhanced_config)
+    Ok((args, jobs))
 }
@@ -1,11 +1,15 @@
-use regex::RegexSet;
-
 use super::{
-    common::{DestinationRepository, SourceRepository},
-    reader,
+    common::{
+        ConnectionConfig, CredentialsConfig, DestinationRepository, PullRequestConfig,
+        SourceRepository,
+    },
+    glob_expression::parse_glob_expression,
+    reader::{self, WorkDirConfig},
+    token::Token,
 };
+use crate::error::{Error, Result};
 use regex::Regex;
-use std::fmt::Debug;
+use std::{collections::HashMap, fmt::Debug, path::PathBuf};
 
 #[derive(Clone, Debug, PartialEq)]
 
@@ -20,6 +24,34 @@ pub struct ReplaceArgs {
     pub after: String,
 }
 
+/// `after` may refer to the capture groups of `before`, e.g. `$1` or `${name}`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RenameArgs {
+    pub before: CustomRegex,
+    pub after: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DeleteArgs {
+    pub pattern: glob::Pattern,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PrependArgs {
+    pub text: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct AppendArgs {
+    pub text: String,
+}
+
+/// Extra variables for the template, on top of the ones syncy provides.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TemplateArgs {
+    pub variables: HashMap<String, String>,
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct UpdateByPatternArgs {
     pub pattern: CustomRegex,
@@ -37,16 +69,173 @@ impl PartialEq for CustomRegex {
     fn eq(&self, other: &Self) -> bool {
         self.0.as_str() == other.0.as_str()
     }
-
-    fn ne(&self, other: &Self) -> bool {
-        self.0.as_str() != other.0.as_str()
-    }
 }
 
 #[derive(Clone, Debug, PartialEq)]
+/// A transformation applies to every file, unless `paths` limits it to the
+/// files matching the `include`/`exclude` globs of the transformation. The
+/// globs see paths as left by the previous transformations.
 pub enum Transformation {
-    Move { args: MoveArgs },
-    Replace { args: ReplaceArgs },
+    Move {
+        args: MoveArgs,
+        paths: Option<GlobExpression>,
+    },
+    Replace {
+        args: ReplaceArgs,
+        paths: Option<GlobExpression>,
+    },
+    Rename {
+        args: RenameArgs,
+        paths: Option<GlobExpression>,
+    },
+    Delete {
+        args: DeleteArgs,
+        paths: Option<GlobExpression>,
+    },
+    Prepend {
+        args: PrependArgs,
+        paths: Option<GlobExpression>,
+    },
+    Append {
+        args: AppendArgs,
+        paths: Option<GlobExpression>,
+    },
+    Template {
+        args: TemplateArgs,
+        paths: Option<GlobExpression>,
+    },
+}
+
+impl Transformation {
+    /// Checks whether the transformation should be applied to a file at `path`.
+    pub fn applies_to(&self, path: &str) -> bool {
+        let paths = match self {
+            Transformation::Move { paths, .. }
+            | Transformation::Replace { paths, .. }
+            | Transformation::Rename { paths, .. }
+            | Transformation::Delete { paths, .. }
+            | Transformation::Prepend { paths, .. }
+            | Transformation::Append { paths, .. }
+            | Transformation::Template { paths, .. } => paths,
+        };
+
+        paths.as_ref().is_none_or(|paths| paths.matches(path))
+    }
+
+    /// Returns the transformation undoing this one, for syncing a destination
+    /// back to the source. Only moves and replaces of literal text can be undone.
+    pub fn reverse(&self) -> Result<Transformation> {
+        match self {
+            Transformation::Move { args, paths } => {
+                // the globs match the paths before the move, which the reversed move never sees
+                if paths.is_some() {
+                    return Err(Error::Config(
+                        "builtin.move with include/exclude can't be reversed".to_string(),
+                    ));
+                }
+
+                Ok(Transformation::Move {
+                    args: MoveArgs {
+                        before: args.after.clone(),
+                        after: args.before.clone(),
+                    },
+                    paths: None,
+                })
+            }
+            Transformation::Replace { args, paths } => {
+                let before = get_literal_text(args.before.0.as_str()).ok_or_else(|| {
+                    Error::Config(format!(
+                        "builtin.replace `{}` can't be reversed, before should be literal text",
+                        args.before.0.as_str()
+                    ))
+                })?;
+                let after = get_literal_replacement(&args.after)
+                    .filter(|after| !after.is_empty())
+                    .ok_or_else(|| {
+                        Error::Config(format!(
+                            "builtin.replace `{}` can't be reversed, after should be non-empty literal text",
+                            args.before.0.as_str()
+                        ))
+                    })?;
+
+                Ok(Transformation::Replace {
+                    args: ReplaceArgs {
+                        before: CustomRegex(Regex::new(&regex::escape(&after))?),
+                        after: before.replace('$', "$$"),
+                    },
+                    paths: paths.clone(),
+                })
+            }
+            Transformation::Rename { .. } => Err(not_reversible("builtin.rename")),
+            Transformation::Delete { .. } => Err(not_reversible("builtin.delete")),
+            Transformation::Prepend { .. } => Err(not_reversible("builtin.prepend")),
+            Transformation::Append { .. } => Err(not_reversible("builtin.append")),
+            Transformation::Template { .. } => Err(not_reversible("builtin.template")),
+        }
+    }
+}
+
+fn not_reversible(fn_name: &str) -> Error {
+    Error::Config(format!(
+        "{} can't be reversed, only builtin.move and builtin.replace can",
+        fn_name
+    ))
+}
+
+/// Returns the text matched by `pattern` when it has no regex syntax besides
+/// escaped punctuation, e.g. `v1\.0` matches only `v1.0`.
+fn get_literal_text(pattern: &str) -> Option<String> {
+    let mut text = String::with_capacity(pattern.len());
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(escaped) if escaped.is_ascii_punctuation() => text.push(escaped),
+                _ => return None,
+            },
+            '.' | '+' | '*' | '?' | '(' | ')' | '|' | '[' | ']' | '{' | '}' | '^' | '$' => {
+                return None
+            }
+            c => text.push(c),
+        }
+    }
+
+    Some(text)
+}
+
+/// Returns the text inserted by a replacement without capture group references,
+/// `$$` being an escaped `$`.
+fn get_literal_replacement(replacement: &str) -> Option<String> {
+    let mut text = String::with_capacity(replacement.len());
+    let mut chars = replacement.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '$' => match chars.next() {
+                Some('$') => text.push('$'),
+                _ => return None,
+            },
+            c => text.push(c),
+        }
+    }
+
+    Some(text)
+}
+
+/// Reverses a list of transformations: every transformation is undone, the
+/// last one first.
+pub fn reverse_transformations(
+    transformations: &Option<Vec<Transformation>>,
+) -> Result<Option<Vec<Transformation>>> {
+    match transformations {
+        Some(transformations) => Ok(Some(
+            transformations
+                .iter()
+                .rev()
+                .map(Transformation::reverse)
+                .collect::<Result<Vec<Transformation>>>()?,
+        )),
+        None => Ok(None),
+    }
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -54,11 +243,158 @@ pub struct ParsedConfig {
     pub version: String,
     pub source: SourceRepository,
     pub destinations: Vec<DestinationRepository>,
-    pub token: String,
+    pub credentials: Credentials,
+    pub api_url: Option<String>,
+    pub web_url: Option<String>,
     pub destination_files: WorkDirExpression,
     pub origin_files: WorkDirExpression,
     pub transformations: Option<Vec<Transformation>>,
     pub update_fns: Option<Vec<UpdateFn>>,
+    pub max_concurrency: usize,
+    pub pull_request: PullRequestConfig,
+    /// Name of the job in the `jobs` list, a config without `jobs` has a single unnamed job
+    pub job: Option<String>,
+}
+
+/// How syncy authenticates against GitHub.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Credentials {
+    Token(Token),
+    /// A GitHub App, which gets an installation token for every repository owner
+    App(GithubApp),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GithubApp {
+    pub id: u64,
+    pub private_key_path: PathBuf,
+}
+
+impl Credentials {
+    /// Hides the token from `message`. An app only has short-lived installation
+    /// tokens, which never leave octocrab.
+    pub fn redact(&self, message: &str) -> String {
+        match self {
+            Credentials::Token(token) => token.redact(message),
+            Credentials::App(_) => message.to_string(),
+        }
+    }
+}
+
+/// How to reach a repository, once its own settings fell back to the top-level ones.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Connection {
+    /// `None` stands for `https://api.github.com`
+    pub api_url: Option<String>,
+    pub web_url: String,
+    pub credentials: Credentials,
+}
+
+pub const DEFAULT_WEB_URL: &str = "https://github.com";
+
+/// `https://api.github.com` is served from `https://github.com`, while a GitHub
+/// Enterprise Server serves its API at `/api/v3` of the web root.
+fn get_web_url(api_url: &str) -> String {
+    let api_url = api_url.trim_end_matches('/');
+    if api_url == "https://api.github.com" {
+        return DEFAULT_WEB_URL.to_string();
+    }
+
+    api_url
+        .strip_suffix("/api/v3")
+        .unwrap_or(api_url)
+        .to_string()
+}
+
+impl ParsedConfig {
+    /// Resolves the connection of a repository from its `connection` settings,
+    /// taking what is not set there from the top level of the config.
+    pub fn get_connection(&self, connection: &ConnectionConfig) -> Connection {
+        let web_url = connection
+            .web_url
+            .clone()
+            .or_else(|| connection.api_url.as_deref().map(get_web_url))
+            .or_else(|| self.web_url.clone())
+            .or_else(|| self.api_url.as_deref().map(get_web_url))
+            .unwrap_or_else(|| DEFAULT_WEB_URL.to_string());
+
+        // the credentials of every repository were validated by parse_config
+        let credentials = parse_credentials(&connection.credentials)
+            .ok()
+            .flatten()
+            .unwrap_or_else(|| self.credentials.clone());
+
+        Connection {
+            api_url: connection.api_url.clone().or_else(|| self.api_url.clone()),
+            web_url,
+            credentials,
+        }
+    }
+
+    /// Hides the tokens of the config, including the ones of single repositories, from `message`.
+    pub fn redact(&self, message: &str) -> String {
+        std::iter::once(&self.source.connection)
+            .chain(self.destinations.iter().map(|d| &d.connection))
+            .filter_map(|connection| connection.credentials.token.as_ref())
+            .fold(self.credentials.redact(message), |message, token| {
+                token.redact(&message)
+            })
+    }
+
+    /// Returns the config syncing `destination` back to the source: the
+    /// destination files at `base_branch` become the origin files and the
+    /// source repository becomes the only destination, with the source
+    /// `git_ref` as the base branch of the pull request.
+    pub fn reverse(
+        &self,
+        destination: &DestinationRepository,
+        base_branch: &str,
+    ) -> Result<ParsedConfig> {
+        Ok(ParsedConfig {
+            version: self.version.clone(),
+            source: SourceRepository {
+                owner: destination.owner.clone(),
+                name: destination.name.clone(),
+                git_ref: base_branch.to_string(),
+                connection: destination.connection.clone(),
+            },
+            destinations: vec![DestinationRepository {
+                owner: self.source.owner.clone(),
+                name: self.source.name.clone(),
+                git_ref: Some(self.source.git_ref.clone()),
+                connection: self.source.connection.clone(),
+                overrides: Default::default(),
+            }],
+            credentials: self.credentials.clone(),
+            api_url: self.api_url.clone(),
+            web_url: self.web_url.clone(),
+            destination_files: self.origin_files.clone(),
+            origin_files: self.destination_files.clone(),
+            transformations: reverse_transformations(&self.transformations)?,
+            update_fns: self.update_fns.clone(),
+            max_concurrency: self.max_concurrency,
+            pull_request: self.pull_request.clone(),
+            job: self.job.clone(),
+        })
+    }
+
+    /// Returns the config syncing `destination`, where the `destination_files`,
+    /// `transformations` and `pull_request` settings of the destination take
+    /// over the top-level ones.
+    pub fn for_destination(&self, destination: &DestinationRepository) -> Result<ParsedConfig> {
+        let overrides = &destination.overrides;
+
+        let mut config = self.clone();
+        if overrides.destination_files.is_some() {
+            config.destination_files = parse_work_dir_expression(&overrides.destination_files)?;
+        }
+        if overrides.transformations.is_some() {
+            config.transformations = parse_transformations(&overrides.transformations)?;
+        }
+        config.pull_request = self.pull_request.merge(&overrides.pull_request);
+
+        Ok(config)
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -67,133 +403,364 @@ pub enum WorkDirExpression {
     Path(String),
 }
 
+/// Files matching any of the include patterns and none of the exclude patterns.
 #[derive(Debug, Clone, PartialEq)]
-pub enum GlobExpression {
-    Single(glob::Pattern),
-    SingleWithExclude(glob::Pattern, glob::Pattern),
+pub struct GlobExpression {
+    pub include: Vec<glob::Pattern>,
+    pub exclude: Vec<glob::Pattern>,
+}
+
+impl GlobExpression {
+    pub fn matches(&self, path: &str) -> bool {
+        self.include.iter().any(|pattern| pattern.matches(path))
+            && !self.exclude.iter().any(|pattern| pattern.matches(path))
+    }
 }
 
-pub fn parse_config(config: reader::Config) -> ParsedConfig {
-    let origin_files = config.origin_files.unwrap_or("".to_string());
-    let destination_files = config.destination_files.unwrap_or("".to_string());
+impl WorkDirExpression {
+    /// Checks whether a file at `path` (relative to the repository root) belongs to the work dir.
+    pub fn matches(&self, path: &str) -> bool {
+        match self {
+            WorkDirExpression::Path(root_path) => {
+                root_path.is_empty()
+                    || path == root_path
+                    || path.starts_with(&format!("{}/", root_path.trim_end_matches('/')))
+            }
+            WorkDirExpression::Glob(glob_expression) => glob_expression.matches(path),
+        }
+    }
+
+    /// Returns the folders (or files) every matching path starts with, so a
+    /// tree can be listed from there instead of from the repository root.
+    /// An empty string stands for the root.
+    pub fn root_paths(&self) -> Vec<String> {
+        let mut root_paths: Vec<String> = match self {
+            WorkDirExpression::Path(root_path) => {
+                vec![root_path.trim_end_matches('/').to_string()]
+            }
+            WorkDirExpression::Glob(glob_expression) => glob_expression
+                .include
+                .iter()
+                .map(|pattern| get_literal_prefix(pattern.as_str()))
+                .collect(),
+        };
+
+        // a root path nested in another one is already listed with it
+        root_paths.sort();
+        let mut result: Vec<String> = Vec::new();
+        for root_path in root_paths {
+            let is_nested = result.iter().any(|parent| {
+                parent.is_empty()
+                    || root_path == *parent
+                    || root_path.starts_with(&format!("{}/", parent))
+            });
+            if !is_nested {
+                result.push(root_path);
+            }
+        }
+
+        result
+    }
+}
+
+/// Returns the longest folder of the pattern without any wildcards,
+/// e.g. `docs/api` for `docs/api/**/*.md`.
+fn get_literal_prefix(pattern: &str) -> String {
+    let components: Vec<&str> = pattern.split('/').collect();
+
+    // the last component is a file name or a wildcard, never a folder to start from
+    let folders = &components[..components.len() - 1];
+    folders
+        .iter()
+        .take_while(|component| !component.contains(['*', '?', '[']))
+        .copied()
+        .collect::<Vec<&str>>()
+        .join("/")
+}
 
-    let origin_files_glob = parse_work_dir_expression(&origin_files);
-    let destination_files_glob = parse_work_dir_expression(&destination_files);
+pub const DEFAULT_MAX_CONCURRENCY: usize = 8;
+
+pub fn parse_config(config: reader::Config) -> Result<ParsedConfig> {
+    let origin_files_glob = parse_work_dir_expression(&config.origin_files)?;
+    let destination_files_glob = parse_work_dir_expression(&config.destination_files)?;
+
+    let max_concurrency = config.max_concurrency.unwrap_or(DEFAULT_MAX_CONCURRENCY);
+    if max_concurrency == 0 {
+        return Err(Error::Config(
+            "max_concurrency should be greater than 0".to_string(),
+        ));
+    }
 
-    ParsedConfig {
+    parse_credentials(&config.source.connection.credentials)?;
+    for destination in config.destinations.iter() {
+        parse_credentials(&destination.connection.credentials)?;
+    }
+    let credentials = parse_credentials(&config.connection.credentials)?.ok_or_else(|| {
+        Error::Config(
+            "missing token: pass --token, set SYNCY_TOKEN or GITHUB_TOKEN, or set token, token_file, token_command or app"
+                .to_string(),
+        )
+    })?;
+
+    let parsed_config = ParsedConfig {
         version: config.version,
         source: config.source,
         destinations: config.destinations,
-        token: config.token,
+        credentials,
+        api_url: config.connection.api_url,
+        web_url: config.connection.web_url,
         destination_files: destination_files_glob,
         origin_files: origin_files_glob,
-        transformations: parse_transformations(&config.transformations),
-        update_fns: parse_update_fns(&config.update_fns),
+        transformations: parse_transformations(&config.transformations)?,
+        update_fns: parse_update_fns(&config.update_fns)?,
+        max_concurrency,
+        pull_request: config.pull_request,
+        job: None,
+    };
+
+    for (index, destination) in parsed_config.destinations.iter().enumerate() {
+        // both would push to the same branch and pull request
+        let is_listed_before = parsed_config.destinations[..index].iter().any(|other| {
+            other.owner.eq_ignore_ascii_case(&destination.owner)
+                && other.name.eq_ignore_ascii_case(&destination.name)
+        });
+        if is_listed_before {
+            return Err(Error::Config(format!(
+                "destination {}/{} is listed twice",
+                destination.owner, destination.name
+            )));
+        }
+
+        parsed_config.for_destination(destination)?;
+    }
+
+    Ok(parsed_config)
+}
+
+fn get_fn_name<'a>(value: &'a serde_json::Value, section: &str) -> Result<&'a str> {
+    value
+        .get("fn")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| Error::Config(format!("{} should contain fn property", section)))
+}
+
+fn get_string_arg(value: &serde_json::Value, fn_name: &str, arg: &str) -> Result<String> {
+    value
+        .get("args")
+        .and_then(|v| v.get(arg))
+        .and_then(|v| v.as_str())
+        .map(|v| v.to_owned())
+        .ok_or_else(|| Error::Config(format!("{}.args should contain {}", fn_name, arg)))
+}
+
+/// Reads a single pattern or a list of patterns, a missing value is empty.
+fn get_patterns(value: &serde_json::Value, fn_name: &str, field: &str) -> Result<Vec<String>> {
+    let patterns = match value.get(field) {
+        Some(patterns) => patterns,
+        None => return Ok(Vec::new()),
+    };
+
+    let patterns = match patterns {
+        serde_json::Value::String(pattern) => Some(vec![pattern.to_owned()]),
+        serde_json::Value::Array(patterns) => patterns
+            .iter()
+            .map(|pattern| pattern.as_str().map(|pattern| pattern.to_owned()))
+            .collect(),
+        _ => None,
+    };
+
+    patterns.ok_or_else(|| {
+        Error::Config(format!(
+            "{}.{} should be a glob or a list of globs",
+            fn_name, field
+        ))
+    })
+}
+
+/// Reads the `include`/`exclude` globs a transformation is scoped to. Only
+/// excluding paths means the transformation applies to everything else.
+fn get_paths(value: &serde_json::Value, fn_name: &str) -> Result<Option<GlobExpression>> {
+    let mut include = get_patterns(value, fn_name, "include")?;
+    let exclude = get_patterns(value, fn_name, "exclude")?;
+
+    if include.is_empty() && exclude.is_empty() {
+        return Ok(None);
+    }
+    if include.is_empty() {
+        include.push("**".to_string());
+    }
+
+    Ok(Some(parse_glob_patterns(&include, &exclude)?))
+}
+
+/// Reads an optional map of strings, a missing map is empty.
+fn get_string_map_arg(
+    value: &serde_json::Value,
+    fn_name: &str,
+    arg: &str,
+) -> Result<HashMap<String, String>> {
+    let map = match value.get("args").and_then(|v| v.get(arg)) {
+        Some(map) => map,
+        None => return Ok(HashMap::new()),
+    };
+
+    map.as_object()
+        .and_then(|object| {
+            object
+                .iter()
+                .map(|(key, value)| Some((key.to_owned(), value.as_str()?.to_owned())))
+                .collect()
+        })
+        .ok_or_else(|| {
+            Error::Config(format!(
+                "{}.args.{} should be a map of strings",
+                fn_name, arg
+            ))
+        })
+}
+
+/// `token_file` and `token_command` are already read into `token` by
+/// `token::resolve_tokens`, so only `token` and `app` are left to choose from.
+fn parse_credentials(credentials: &CredentialsConfig) -> Result<Option<Credentials>> {
+    match (&credentials.token, &credentials.app) {
+        (Some(token), None) => Ok(Some(Credentials::Token(token.clone()))),
+        (None, Some(app)) => Ok(Some(Credentials::App(GithubApp {
+            id: app.id,
+            private_key_path: app.private_key_path.clone(),
+        }))),
+        (Some(_), Some(_)) => Err(Error::Config(
+            "only one of token, token_file, token_command and app should be set".to_string(),
+        )),
+        (None, None) => Ok(None),
     }
 }
 
 fn parse_transformations(
     transformations: &Option<Vec<serde_json::Value>>,
-) -> Option<Vec<Transformation>> {
+) -> Result<Option<Vec<Transformation>>> {
     if let Some(unwrapped_transformations) = transformations {
         let mut parsed_transformations = Vec::new();
         for t in unwrapped_transformations.iter() {
-            let parsed_transformation = &t
-                .get("fn")
-                .and_then(|v| {
-                    if v == "builtin.move" {
-                        let before = t
-                            .get("args")
-                            .and_then(|v| v.get("before"))
-                            .expect("builtin.move.args should contain before")
-                            .as_str()
-                            .unwrap()
-                            .to_owned();
-
-                        let after = t
-                            .get("args")
-                            .and_then(|v| v.get("after"))
-                            .expect("builtin.move.args should contain after")
-                            .as_str()
-                            .unwrap()
-                            .to_owned();
-                        let args = MoveArgs { before, after };
-                        Some(Transformation::Move { args })
-                    } else if v == "builtin.replace" {
-                        let before = t
-                            .get("args")
-                            .and_then(|v| v.get("before"))
-                            .expect("builtin.replace.args should contain before")
-                            .as_str()
-                            .unwrap()
-                            .to_owned();
-
-                        let after = t
-                            .get("args")
-                            .and_then(|v| v.get("after"))
-                            .expect("builtin.replace.args should contain after")
-                            .as_str()
-                            .unwrap()
-                            .to_owned();
-
-                        let args = ReplaceArgs {
-                            before: CustomRegex(Regex::new(&before).unwrap()),
-                            after,
-                        };
-                        Some(Transformation::Replace { args })
-                    } else {
-                        panic!("transformations.fn should be one of reserved functions")
-                    }
-                })
-                .expect("transformation should contain fn property");
+            let fn_name = get_fn_name(t, "transformation")?;
+            let paths = get_paths(t, fn_name)?;
+
+            let parsed_transformation = match fn_name {
+                "builtin.move" => {
+                    let before = get_string_arg(t, fn_name, "before")?;
+                    let after = get_string_arg(t, fn_name, "after")?;
+
+                    let args = MoveArgs { before, after };
+                    Transformation::Move { args, paths }
+                }
+                "builtin.replace" => {
+                    let before = get_string_arg(t, fn_name, "before")?;
+                    let after = get_string_arg(t, fn_name, "after")?;
+
+                    let args = ReplaceArgs {
+                        before: CustomRegex(Regex::new(&before)?),
+                        after,
+                    };
+                    Transformation::Replace { args, paths }
+                }
+                "builtin.rename" => {
+                    let before = get_string_arg(t, fn_name, "before")?;
+                    let after = get_string_arg(t, fn_name, "after")?;
+
+                    let args = RenameArgs {
+                        before: CustomRegex(Regex::new(&before)?),
+                        after,
+                    };
+                    Transformation::Rename { args, paths }
+                }
+                "builtin.delete" | "builtin.exclude" => {
+                    let pattern = get_string_arg(t, fn_name, "pattern")?;
+
+                    let args = DeleteArgs {
+                        pattern: parse_glob_pattern(&pattern)?,
+                    };
+                    Transformation::Delete { args, paths }
+                }
+                "builtin.prepend" => {
+                    let text = get_string_arg(t, fn_name, "text")?;
+
+                    let args = PrependArgs { text };
+                    Transformation::Prepend { args, paths }
+                }
+                "builtin.append" => {
+                    let text = get_string_arg(t, fn_name, "text")?;
+
+                    let args = AppendArgs { text };
+                    Transformation::Append { args, paths }
+                }
+                "builtin.template" => {
+                    let args = TemplateArgs {
+                        variables: get_string_map_arg(t, fn_name, "variables")?,
+                    };
+                    Transformation::Template { args, paths }
+                }
+                _ => {
+                    return Err(Error::Config(
+                        "transformations.fn should be one of reserved functions".to_string(),
+                    ))
+                }
+            };
 
-            parsed_transformations.push(parsed_transformation.to_owned());
+            parsed_transformations.push(parsed_transformation);
         }
 
-        Some(parsed_transformations)
+        Ok(Some(parsed_transformations))
     } else {
-        None
+        Ok(None)
     }
 }
 
-fn parse_update_fns(update_fns: &Option<Vec<serde_json::Value>>) -> Option<Vec<UpdateFn>> {
+fn parse_update_fns(update_fns: &Option<Vec<serde_json::Value>>) -> Result<Option<Vec<UpdateFn>>> {
     if let Some(unwrapped_update_fns) = update_fns {
         let mut parsed_fns = Vec::new();
         for r#fn in unwrapped_update_fns.iter() {
-            let parsed_fn = &r#fn
-                .get("fn")
-                .and_then(|v| {
-                    if v == "builtin.update_by_pattern" {
-                        let pattern = r#fn
-                            .get("args")
-                            .and_then(|v| v.get("pattern"))
-                            .expect("builtin.update_by_pattern.args should contain pattern")
-                            .as_str()
-                            .unwrap()
-                            .to_owned();
-
-                        let args = UpdateByPatternArgs { pattern: CustomRegex(Regex::new(&pattern).unwrap())  };
-                        Some(UpdateFn::UpdateByPattern { args })
-                    } else {
-                        panic!("update_fns.fn should be one of reserved functions")
-                    }
-                })
-                .expect("transformation should contain fn property");
+            let fn_name = get_fn_name(r#fn, "update_fn")?;
+
+            let parsed_fn = match fn_name {
+                "builtin.update_by_pattern" => {
+                    let pattern = get_string_arg(r#fn, fn_name, "pattern")?;
+
+                    let args = UpdateByPatternArgs {
+                        pattern: CustomRegex(Regex::new(&pattern)?),
+                    };
+                    UpdateFn::UpdateByPattern { args }
+                }
+                _ => {
+                    return Err(Error::Config(
+                        "update_fns.fn should be one of reserved functions".to_string(),
+                    ))
+                }
+            };
 
-            parsed_fns.push(parsed_fn.to_owned());
+            parsed_fns.push(parsed_fn);
         }
 
-        Some(parsed_fns)
+        Ok(Some(parsed_fns))
     } else {
-        None
+        Ok(None)
     }
 }
 
-fn parse_work_dir_expression(val: &str) -> WorkDirExpression {
-    if val.starts_with("glob(") {
-        parse_glob_expression(val)
-    } else {
-        parse_path_expression(val)
+fn parse_work_dir_expression(val: &Option<WorkDirConfig>) -> Result<WorkDirExpression> {
+    match val {
+        None => Ok(parse_path_expression("")),
+        Some(WorkDirConfig::Expression(expression))
+            if expression.trim_start().starts_with("glob(") =>
+        {
+            let args = parse_glob_expression(expression)?;
+            Ok(WorkDirExpression::Glob(parse_glob_patterns(
+                &args.include,
+                &args.exclude,
+            )?))
+        }
+        Some(WorkDirConfig::Expression(path)) => Ok(parse_path_expression(path)),
+        Some(WorkDirConfig::Glob { include, exclude }) => Ok(WorkDirExpression::Glob(
+            parse_glob_patterns(include, exclude)?,
+        )),
     }
 }
 
@@ -201,43 +768,30 @@ fn parse_path_expression(val: &str) -> WorkDirExpression {
     WorkDirExpression::Path(val.to_string())
 }
 
-fn parse_glob_expression(val: &str) -> WorkDirExpression {
-    let re_set = RegexSet::new(&["glob\\(\".*?\", \".*?\"\\)", "glob\\(\".*?\"\\)"]).unwrap();
-    let result = re_set.matches(&val);
-
-    let matched_any = result.matched_any();
-    let single_with_exclude = result.matched(0);
-    let single = result.matched(1);
-
-    let len = &val.len();
-
-    if matched_any && single_with_exclude {
-        let comma_position = &val.find(",").unwrap();
-
-        let first_glob_end = comma_position - 1;
-        let glob_pattern = glob::Pattern::new(&val[6..first_glob_end]).unwrap();
-
-        let start_second_glob = comma_position + 3;
-        let end_second_glob = len - 2;
-
-        let second_glob_pattern =
-            glob::Pattern::new(&val[start_second_glob..end_second_glob]).unwrap();
+fn parse_glob_pattern(pattern: &str) -> Result<glob::Pattern> {
+    glob::Pattern::new(pattern).map_err(|source| Error::Glob {
+        pattern: pattern.to_string(),
+        source,
+    })
+}
 
-        return WorkDirExpression::Glob(GlobExpression::SingleWithExclude(
-            glob_pattern,
-            second_glob_pattern,
+fn parse_glob_patterns(include: &[String], exclude: &[String]) -> Result<GlobExpression> {
+    if include.is_empty() {
+        return Err(Error::Config(
+            "glob should include at least one pattern".to_string(),
         ));
     }
 
-    if matched_any && single {
-        let end = len - 2;
-        let pattern = &val[6..end];
-
-        let glob_pattern = glob::Pattern::new(pattern).unwrap();
-        return WorkDirExpression::Glob(GlobExpression::Single(glob_pattern));
-    }
-
-    panic!("invalid glob string");
+    Ok(GlobExpression {
+        include: include
+            .iter()
+            .map(|pattern| parse_glob_pattern(pattern))
+            .collect::<Result<_>>()?,
+        exclude: exclude
+            .iter()
+            .map(|pattern| parse_glob_pattern(pattern))
+            .collect::<Result<_>>()?,
+    })
 }
 
 #[cfg(test)]
@@ -245,15 +799,18 @@ mod tests {
     use serde_json::json;
 
     use super::{
-        parse_config, GlobExpression, MoveArgs, ParsedConfig, ReplaceArgs, Transformation,
-        WorkDirExpression,
+        parse_config, Credentials, GithubApp, MoveArgs, ParsedConfig, ReplaceArgs, Token,
+        Transformation, WorkDirExpression, DEFAULT_MAX_CONCURRENCY,
     };
     use crate::cli::reader::read_config;
-    use crate::fixtures::workdir_path::create_glob_single;
+    use crate::error::Error;
+    use crate::fixtures::workdir_path::{create_glob, create_glob_single};
     use crate::{
         cli::{
-            common::{DestinationRepository, SourceRepository},
-            reader::Config,
+            common::{
+                ConnectionConfig, CredentialsConfig, DestinationRepository, SourceRepository,
+            },
+            reader::{Config, WorkDirConfig},
         },
         fixtures::workdir_path::create_glob_single_with_exclude,
     };
@@ -265,11 +822,15 @@ mod tests {
             owner: "my_name".to_string(),
             name: "test1".to_string(),
             git_ref: "main".to_string(),
+            connection: Default::default(),
         };
 
         let expected_destination = DestinationRepository {
             owner: "my_name".to_string(),
             name: "test2".to_string(),
+            git_ref: None,
+            connection: Default::default(),
+            overrides: Default::default(),
         };
 
         let transformation_args = json!({
@@ -285,14 +846,24 @@ mod tests {
             version: "0.0.1".to_string(),
             source: expected_source.clone(),
             destinations: vec![expected_destination.clone()],
-            token: "random_token".to_string(),
-            origin_files: Some("glob(\"**\")".to_string()),
-            destination_files: Some("glob(\"my_folder/**\")".to_string()),
+            connection: ConnectionConfig {
+                credentials: CredentialsConfig {
+                    token: Some(Token::from("random_token")),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            origin_files: Some(WorkDirConfig::Expression("glob(\"**\")".to_string())),
+            destination_files: Some(WorkDirConfig::Expression(
+                "glob(\"my_folder/**\")".to_string(),
+            )),
             transformations: Some(vec![transformation]),
-            update_fns: None
+            update_fns: None,
+            max_concurrency: None,
+            pull_request: Default::default(),
         };
 
-        let parsed_config = parse_config(config.clone());
+        let parsed_config = parse_config(config.clone()).unwrap();
 
         let expected_transformation_args = MoveArgs {
             before: "".to_string(),
@@ -300,16 +871,22 @@ mod tests {
         };
         let expected_transformation = Transformation::Move {
             args: expected_transformation_args,
+            paths: None,
         };
         let expected_config = ParsedConfig {
             version: "0.0.1".to_string(),
             source: expected_source,
             destinations: vec![expected_destination],
-            token: "random_token".to_string(),
+            credentials: Credentials::Token(Token::from("random_token")),
+            api_url: None,
+            web_url: None,
             origin_files: create_glob_single("**"),
             destination_files: create_glob_single("my_folder/**"),
             transformations: Some(vec![expected_transformation]),
-            update_fns: None
+            update_fns: None,
+            max_concurrency: DEFAULT_MAX_CONCURRENCY,
+            pull_request: Default::default(),
+            job: None,
         };
 
         assert_eq!(parsed_config, expected_config)
@@ -321,11 +898,15 @@ mod tests {
             owner: "my_name".to_string(),
             name: "test1".to_string(),
             git_ref: "main".to_string(),
+            connection: Default::default(),
         };
 
         let expected_destination = DestinationRepository {
             owner: "my_name".to_string(),
             name: "test2".to_string(),
+            git_ref: None,
+            connection: Default::default(),
+            overrides: Default::default(),
         };
 
         let transformation_args = json!({
@@ -340,14 +921,26 @@ mod tests {
             version: "0.0.1".to_string(),
             source: expected_source.clone(),
             destinations: vec![expected_destination.clone()],
-            token: "random_token".to_string(),
-            origin_files: Some("glob(\"**\", \"readme\")".to_string()),
-            destination_files: Some("glob(\"my_folder/**\", \"my_folder/dist/**\")".to_string()),
+            connection: ConnectionConfig {
+                credentials: CredentialsConfig {
+                    token: Some(Token::from("random_token")),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            origin_files: Some(WorkDirConfig::Expression(
+                "glob(\"**\", \"readme\")".to_string(),
+            )),
+            destination_files: Some(WorkDirConfig::Expression(
+                "glob(\"my_folder/**\", \"my_folder/dist/**\")".to_string(),
+            )),
             transformations: Some(vec![transformation]),
-            update_fns: None
+            update_fns: None,
+            max_concurrency: None,
+            pull_request: Default::default(),
         };
 
-        let parsed_config = parse_config(config.clone());
+        let parsed_config = parse_config(config.clone()).unwrap();
 
         let expected_transformation_args = MoveArgs {
             before: "".to_string(),
@@ -355,16 +948,22 @@ mod tests {
         };
         let expected_transformation = Transformation::Move {
             args: expected_transformation_args,
+            paths: None,
         };
         let expected_config = ParsedConfig {
             version: "0.0.1".to_string(),
             source: expected_source,
             destinations: vec![expected_destination],
-            token: "random_token".to_string(),
+            credentials: Credentials::Token(Token::from("random_token")),
+            api_url: None,
+            web_url: None,
             origin_files: create_glob_single_with_exclude("**", "readme"),
             destination_files: create_glob_single_with_exclude("my_folder/**", "my_folder/dist/**"),
             transformations: Some(vec![expected_transformation]),
-            update_fns: None
+            update_fns: None,
+            max_concurrency: DEFAULT_MAX_CONCURRENCY,
+            pull_request: Default::default(),
+            job: None,
         };
 
         assert_eq!(parsed_config, expected_config)
@@ -376,11 +975,15 @@ mod tests {
             owner: "my_name".to_string(),
             name: "test1".to_string(),
             git_ref: "main".to_string(),
+            connection: Default::default(),
         };
 
         let expected_destination = DestinationRepository {
             owner: "my_name".to_string(),
             name: "test2".to_string(),
+            git_ref: None,
+            connection: Default::default(),
+            overrides: Default::default(),
         };
 
         let transformation_args = json!({
@@ -396,14 +999,22 @@ mod tests {
             version: "0.0.1".to_string(),
             source: expected_source.clone(),
             destinations: vec![expected_destination.clone()],
-            token: "random_token".to_string(),
+            connection: ConnectionConfig {
+                credentials: CredentialsConfig {
+                    token: Some(Token::from("random_token")),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
             origin_files: None,
             destination_files: None,
             transformations: Some(vec![transformation]),
-            update_fns: None
+            update_fns: None,
+            max_concurrency: None,
+            pull_request: Default::default(),
         };
 
-        let parsed_config = parse_config(config.clone());
+        let parsed_config = parse_config(config.clone()).unwrap();
 
         let expected_transformation_args = MoveArgs {
             before: "".to_string(),
@@ -411,17 +1022,23 @@ mod tests {
         };
         let expected_transformation = Transformation::Move {
             args: expected_transformation_args,
+            paths: None,
         };
 
         let expected_config = ParsedConfig {
             version: "0.0.1".to_string(),
             source: expected_source,
             destinations: vec![expected_destination],
-            token: "random_token".to_string(),
+            credentials: Credentials::Token(Token::from("random_token")),
+            api_url: None,
+            web_url: None,
             origin_files: WorkDirExpression::Path("".to_string()),
             destination_files: WorkDirExpression::Path("".to_string()),
             transformations: Some(vec![expected_transformation]),
-            update_fns: None
+            update_fns: None,
+            max_concurrency: DEFAULT_MAX_CONCURRENCY,
+            pull_request: Default::default(),
+            job: None,
         };
 
         assert_eq!(parsed_config, expected_config)
@@ -433,11 +1050,15 @@ mod tests {
             owner: "my_name".to_string(),
             name: "test1".to_string(),
             git_ref: "main".to_string(),
+            connection: Default::default(),
         };
 
         let expected_destination = DestinationRepository {
             owner: "my_name".to_string(),
             name: "test2".to_string(),
+            git_ref: None,
+            connection: Default::default(),
+            overrides: Default::default(),
         };
 
         let transformation_args = json!({
@@ -452,14 +1073,22 @@ mod tests {
             version: "0.0.1".to_string(),
             source: expected_source.clone(),
             destinations: vec![expected_destination.clone()],
-            token: "random_token".to_string(),
-            origin_files: Some("path1".to_string()),
-            destination_files: Some("path2".to_string()),
+            connection: ConnectionConfig {
+                credentials: CredentialsConfig {
+                    token: Some(Token::from("random_token")),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            origin_files: Some(WorkDirConfig::Expression("path1".to_string())),
+            destination_files: Some(WorkDirConfig::Expression("path2".to_string())),
             transformations: Some(vec![transformation]),
-            update_fns: None
+            update_fns: None,
+            max_concurrency: None,
+            pull_request: Default::default(),
         };
 
-        let parsed_config = parse_config(config.clone());
+        let parsed_config = parse_config(config.clone()).unwrap();
 
         let expected_transformation_args = MoveArgs {
             before: "".to_string(),
@@ -467,23 +1096,28 @@ mod tests {
         };
         let expected_transformation = Transformation::Move {
             args: expected_transformation_args,
+            paths: None,
         };
         let expected_config = ParsedConfig {
             version: "0.0.1".to_string(),
             source: expected_source,
             destinations: vec![expected_destination],
-            token: "random_token".to_string(),
+            credentials: Credentials::Token(Token::from("random_token")),
+            api_url: None,
+            web_url: None,
             origin_files: WorkDirExpression::Path("path1".to_string()),
             destination_files: WorkDirExpression::Path("path2".to_string()),
             transformations: Some(vec![expected_transformation]),
-            update_fns: None
+            update_fns: None,
+            max_concurrency: DEFAULT_MAX_CONCURRENCY,
+            pull_request: Default::default(),
+            job: None,
         };
 
         assert_eq!(parsed_config, expected_config)
     }
 
     #[test]
-    #[should_panic(expected = "builtin.move.args should contain after")]
     fn test_transformations_move_after() {
         let doc = indoc! {r#"
             version: 0.0.1
@@ -508,13 +1142,15 @@ mod tests {
                    before: random  
             "#};
 
-        let config = read_config(&doc).unwrap();
+        let config = read_config(doc).unwrap();
 
-        parse_config(config);
+        assert_eq!(
+            parse_config(config).unwrap_err().to_string(),
+            "invalid config: builtin.move.args should contain after"
+        );
     }
 
     #[test]
-    #[should_panic(expected = "builtin.move.args should contain before")]
     fn test_transformations_move_before() {
         let doc = indoc! {r#"
             version: 0.0.1
@@ -539,13 +1175,15 @@ mod tests {
                     after: random  
             "#};
 
-        let config = read_config(&doc).unwrap();
+        let config = read_config(doc).unwrap();
 
-        parse_config(config);
+        assert_eq!(
+            parse_config(config).unwrap_err().to_string(),
+            "invalid config: builtin.move.args should contain before"
+        );
     }
 
     #[test]
-    #[should_panic(expected = "transformations.fn should be one of reserved functions")]
     fn test_transformations_wrong_fn() {
         let doc = indoc! {r#"
         version: 0.0.1
@@ -568,70 +1206,1069 @@ mod tests {
             - fn: random
         "#};
 
-        let config = read_config(&doc).unwrap();
+        let config = read_config(doc).unwrap();
 
-        parse_config(config);
+        assert_eq!(
+            parse_config(config).unwrap_err().to_string(),
+            "invalid config: transformations.fn should be one of reserved functions"
+        );
     }
 
-    mod transformations_replace {
-        use regex::Regex;
+    #[test]
+    fn test_invalid_glob() {
+        let doc = indoc! {r#"
+        version: 0.0.1
 
-        use crate::cli::parser::CustomRegex;
+        source:
+          owner: my_name
+          name: test1
+          git_ref: main
 
-        use super::*;
-        #[test]
-        fn success() {
-            let expected_source = SourceRepository {
-                owner: "my_name".to_string(),
-                name: "test1".to_string(),
-                git_ref: "main".to_string(),
-            };
+        destinations:
+          - owner: my_name
+            name: test2
 
-            let expected_destination = DestinationRepository {
-                owner: "my_name".to_string(),
-                name: "test2".to_string(),
-            };
+        token: random_token
 
-            let transformation_args = json!({
-                "before": "kek".to_string(),
-                "after": "lol".to_string(),
-            });
-            let transformation = json!({
-                "fn": "builtin.replace",
-                "args": transformation_args,
-            });
-            let config = Config {
-                version: "0.0.1".to_string(),
-                source: expected_source.clone(),
-                destinations: vec![expected_destination.clone()],
-                token: "random_token".to_string(),
-                origin_files: Some("path1".to_string()),
-                destination_files: Some("path2".to_string()),
-                transformations: Some(vec![transformation]),
-                update_fns: None
-            };
+        origin_files: glob("***")
+        "#};
 
-            let parsed_config = parse_config(config.clone());
+        let config = read_config(doc).unwrap();
 
-            let expected_transformation_args = ReplaceArgs {
-                before: CustomRegex(Regex::new("kek").unwrap()),
-                after: "lol".to_string(),
-            };
-            let expected_transformation = Transformation::Replace {
-                args: expected_transformation_args,
-            };
-            let expected_config = ParsedConfig {
-                version: "0.0.1".to_string(),
-                source: expected_source,
-                destinations: vec![expected_destination],
-                token: "random_token".to_string(),
-                origin_files: WorkDirExpression::Path("path1".to_string()),
-                destination_files: WorkDirExpression::Path("path2".to_string()),
-                transformations: Some(vec![expected_transformation]),
-                update_fns: None
-            };
+        assert!(matches!(
+            parse_config(config).unwrap_err(),
+            Error::Glob { pattern, .. } if pattern == "***"
+        ));
+    }
 
-            assert_eq!(parsed_config, expected_config)
+    #[test]
+    fn test_invalid_replace_regex() {
+        let doc = indoc! {r#"
+        version: 0.0.1
+
+        source:
+          owner: my_name
+          name: test1
+          git_ref: main
+
+        destinations:
+          - owner: my_name
+            name: test2
+
+        token: random_token
+
+        transformations:
+            - fn: builtin.replace
+              args:
+                before: "("
+                after: ")"
+        "#};
+
+        let config = read_config(doc).unwrap();
+
+        assert!(matches!(parse_config(config).unwrap_err(), Error::Regex(_)));
+    }
+
+    #[test]
+    fn test_work_dir_matches() {
+        let path = WorkDirExpression::Path("folder".to_string());
+        assert!(path.matches("folder/file"));
+        assert!(path.matches("folder/nested/file"));
+        assert!(!path.matches("folder2/file"));
+        assert!(!path.matches("file"));
+
+        assert!(WorkDirExpression::Path("".to_string()).matches("file"));
+
+        let glob = create_glob_single_with_exclude("folder/**", "folder/dist/**");
+        assert!(glob.matches("folder/file"));
+        assert!(!glob.matches("folder/dist/file"));
+        assert!(!glob.matches("file"));
+    }
+
+    #[test]
+    fn test_work_dir_root_paths() {
+        assert_eq!(
+            create_glob(&["docs/api/**/*.md", "docs/api/v2/**", "src/lib.rs"], &[]).root_paths(),
+            vec!["docs/api".to_string(), "src".to_string()]
+        );
+        assert_eq!(
+            create_glob(&["docs/**", "**/*.md"], &[]).root_paths(),
+            vec!["".to_string()]
+        );
+        assert_eq!(
+            create_glob(&["docs/v[12]/**", "docs/*/index.md"], &[]).root_paths(),
+            vec!["docs".to_string()]
+        );
+        assert_eq!(
+            WorkDirExpression::Path("folder/".to_string()).root_paths(),
+            vec!["folder".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_zero_max_concurrency() {
+        let doc = indoc! {r#"
+        version: 0.0.1
+
+        source:
+          owner: my_name
+          name: test1
+          git_ref: main
+
+        destinations:
+          - owner: my_name
+            name: test2
+
+        token: random_token
+
+        max_concurrency: 0
+        "#};
+
+        let config = read_config(doc).unwrap();
+
+        assert_eq!(
+            parse_config(config).unwrap_err().to_string(),
+            "invalid config: max_concurrency should be greater than 0"
+        );
+    }
+
+    #[test]
+    fn test_multiple_glob_patterns() {
+        let doc = indoc! {r#"
+        version: 0.0.1
+
+        source:
+          owner: my_name
+          name: test1
+          git_ref: main
+
+        destinations:
+          - owner: my_name
+            name: test2
+
+        token: random_token
+
+        origin_files: glob(["src/**", "docs/**"], exclude = ["**/*.tmp", "docs/drafts/**"])
+
+        destination_files:
+          include:
+            - vendor/src/**
+            - vendor/docs/**
+          exclude:
+            - vendor/**/*.tmp
+        "#};
+
+        let config = parse_config(read_config(doc).unwrap()).unwrap();
+
+        assert_eq!(
+            config.origin_files,
+            create_glob(&["src/**", "docs/**"], &["**/*.tmp", "docs/drafts/**"])
+        );
+        assert_eq!(
+            config.destination_files,
+            create_glob(&["vendor/src/**", "vendor/docs/**"], &["vendor/**/*.tmp"])
+        );
+
+        assert!(config.origin_files.matches("src/main.rs"));
+        assert!(config.origin_files.matches("docs/index.md"));
+        assert!(!config.origin_files.matches("docs/drafts/index.md"));
+        assert!(!config.origin_files.matches("src/cache.tmp"));
+        assert!(!config.origin_files.matches("readme"));
+    }
+
+    #[test]
+    fn test_malformed_glob() {
+        let doc = indoc! {r#"
+        version: 0.0.1
+
+        source:
+          owner: my_name
+          name: test1
+          git_ref: main
+
+        destinations:
+          - owner: my_name
+            name: test2
+
+        token: random_token
+
+        origin_files: glob("src/**", exclude = "src/dist/**"
+        "#};
+
+        let config = read_config(doc).unwrap();
+
+        assert_eq!(
+            parse_config(config).unwrap_err().to_string(),
+            "invalid config: invalid glob `glob(\"src/**\", exclude = \"src/dist/**\"`: expected `)` at column 39"
+        );
+    }
+
+    #[test]
+    fn test_empty_glob_include() {
+        let doc = indoc! {r#"
+        version: 0.0.1
+
+        source:
+          owner: my_name
+          name: test1
+          git_ref: main
+
+        destinations:
+          - owner: my_name
+            name: test2
+
+        token: random_token
+
+        origin_files:
+          include: []
+        "#};
+
+        let config = read_config(doc).unwrap();
+
+        assert_eq!(
+            parse_config(config).unwrap_err().to_string(),
+            "invalid config: glob should include at least one pattern"
+        );
+    }
+
+    #[test]
+    fn missing_token() {
+        let doc = indoc! {r#"
+        version: 0.0.1
+
+        source:
+          owner: my_name
+          name: test1
+          git_ref: main
+
+        destinations:
+          - owner: my_name
+            name: test2
+        "#};
+
+        let config = read_config(doc).unwrap();
+
+        assert_eq!(
+            parse_config(config).unwrap_err().to_string(),
+            "invalid config: missing token: pass --token, set SYNCY_TOKEN or GITHUB_TOKEN, or set token, token_file, token_command or app"
+        );
+    }
+
+    #[test]
+    fn redacted_token() {
+        let doc = indoc! {r#"
+        version: 0.0.1
+
+        source:
+          owner: my_name
+          name: test1
+          git_ref: main
+
+        destinations:
+          - owner: my_name
+            name: test2
+
+        token: ghp_secret
+        "#};
+
+        let config = parse_config(read_config(doc).unwrap()).unwrap();
+
+        assert_eq!(
+            config.credentials,
+            Credentials::Token(Token::from("ghp_secret"))
+        );
+        let debug_output = format!("{:?}", config);
+        assert!(!debug_output.contains("ghp_secret"));
+        assert!(debug_output.contains(r#"credentials: Token("***")"#));
+    }
+
+    #[test]
+    fn app_credentials() {
+        let doc = indoc! {r#"
+        version: 0.0.1
+
+        source:
+          owner: my_name
+          name: test1
+          git_ref: main
+
+        destinations:
+          - owner: my_name
+            name: test2
+
+        app:
+          id: 42
+          private_key_path: keys/app.pem
+        "#};
+
+        let config = parse_config(read_config(doc).unwrap()).unwrap();
+
+        assert_eq!(
+            config.credentials,
+            Credentials::App(GithubApp {
+                id: 42,
+                private_key_path: "keys/app.pem".into(),
+            })
+        );
+    }
+
+    mod transformations_replace {
+        use regex::Regex;
+
+        use crate::cli::parser::CustomRegex;
+
+        use super::*;
+        #[test]
+        fn success() {
+            let expected_source = SourceRepository {
+                owner: "my_name".to_string(),
+                name: "test1".to_string(),
+                git_ref: "main".to_string(),
+                connection: Default::default(),
+            };
+
+            let expected_destination = DestinationRepository {
+                owner: "my_name".to_string(),
+                name: "test2".to_string(),
+                git_ref: None,
+                connection: Default::default(),
+                overrides: Default::default(),
+            };
+
+            let transformation_args = json!({
+                "before": "kek".to_string(),
+                "after": "lol".to_string(),
+            });
+            let transformation = json!({
+                "fn": "builtin.replace",
+                "args": transformation_args,
+            });
+            let config = Config {
+                version: "0.0.1".to_string(),
+                source: expected_source.clone(),
+                destinations: vec![expected_destination.clone()],
+                connection: ConnectionConfig {
+                    credentials: CredentialsConfig {
+                        token: Some(Token::from("random_token")),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                origin_files: Some(WorkDirConfig::Expression("path1".to_string())),
+                destination_files: Some(WorkDirConfig::Expression("path2".to_string())),
+                transformations: Some(vec![transformation]),
+                update_fns: None,
+                max_concurrency: None,
+                pull_request: Default::default(),
+            };
+
+            let parsed_config = parse_config(config.clone()).unwrap();
+
+            let expected_transformation_args = ReplaceArgs {
+                before: CustomRegex(Regex::new("kek").unwrap()),
+                after: "lol".to_string(),
+            };
+            let expected_transformation = Transformation::Replace {
+                args: expected_transformation_args,
+                paths: None,
+            };
+            let expected_config = ParsedConfig {
+                version: "0.0.1".to_string(),
+                source: expected_source,
+                destinations: vec![expected_destination],
+                credentials: Credentials::Token(Token::from("random_token")),
+                api_url: None,
+                web_url: None,
+                origin_files: WorkDirExpression::Path("path1".to_string()),
+                destination_files: WorkDirExpression::Path("path2".to_string()),
+                transformations: Some(vec![expected_transformation]),
+                update_fns: None,
+                max_concurrency: DEFAULT_MAX_CONCURRENCY,
+                pull_request: Default::default(),
+                job: None,
+            };
+
+            assert_eq!(parsed_config, expected_config)
+        }
+    }
+
+    mod transformations_builtins {
+        use std::collections::HashMap;
+
+        use regex::Regex;
+
+        use crate::cli::parser::{
+            AppendArgs, CustomRegex, DeleteArgs, PrependArgs, RenameArgs, TemplateArgs,
+        };
+
+        use super::*;
+
+        #[test]
+        fn success() {
+            let doc = indoc! {r#"
+            version: 0.0.1
+
+            source:
+              owner: my_name
+              name: test1
+              git_ref: main
+
+            destinations:
+              - owner: my_name
+                name: test2
+
+            token: random_token
+
+            transformations:
+              - fn: builtin.rename
+                args:
+                  before: ^docs/(.*)\.md$
+                  after: guide/$1.md
+              - fn: builtin.delete
+                args:
+                  pattern: "**/*.tmp"
+              - fn: builtin.exclude
+                args:
+                  pattern: internal/**
+              - fn: builtin.prepend
+                args:
+                  text: "// generated by syncy, do not edit\n"
+              - fn: builtin.append
+                args:
+                  text: "\n"
+              - fn: builtin.template
+              - fn: builtin.template
+                args:
+                  variables:
+                    team: platform
+            "#};
+
+            let config = parse_config(read_config(doc).unwrap()).unwrap();
+
+            assert_eq!(
+                config.transformations,
+                Some(vec![
+                    Transformation::Rename {
+                        args: RenameArgs {
+                            before: CustomRegex(Regex::new("^docs/(.*)\\.md$").unwrap()),
+                            after: "guide/$1.md".to_string(),
+                        },
+                        paths: None,
+                    },
+                    Transformation::Delete {
+                        args: DeleteArgs {
+                            pattern: glob::Pattern::new("**/*.tmp").unwrap(),
+                        },
+                        paths: None,
+                    },
+                    Transformation::Delete {
+                        args: DeleteArgs {
+                            pattern: glob::Pattern::new("internal/**").unwrap(),
+                        },
+                        paths: None,
+                    },
+                    Transformation::Prepend {
+                        args: PrependArgs {
+                            text: "// generated by syncy, do not edit\n".to_string(),
+                        },
+                        paths: None,
+                    },
+                    Transformation::Append {
+                        args: AppendArgs {
+                            text: "\n".to_string(),
+                        },
+                        paths: None,
+                    },
+                    Transformation::Template {
+                        args: TemplateArgs {
+                            variables: HashMap::new(),
+                        },
+                        paths: None,
+                    },
+                    Transformation::Template {
+                        args: TemplateArgs {
+                            variables: HashMap::from([(
+                                "team".to_string(),
+                                "platform".to_string()
+                            )]),
+                        },
+                        paths: None,
+                    },
+                ])
+            );
+        }
+
+        #[test]
+        fn invalid_template_variables() {
+            let doc = indoc! {r#"
+            version: 0.0.1
+
+            source:
+              owner: my_name
+              name: test1
+              git_ref: main
+
+            destinations:
+              - owner: my_name
+                name: test2
+
+            token: random_token
+
+            transformations:
+              - fn: builtin.template
+                args:
+                  variables:
+                    - team
+            "#};
+
+            let config = read_config(doc).unwrap();
+
+            assert_eq!(
+                parse_config(config).unwrap_err().to_string(),
+                "invalid config: builtin.template.args.variables should be a map of strings"
+            );
+        }
+    }
+
+    mod transformations_paths {
+        use crate::fixtures::workdir_path::create_glob;
+
+        use super::*;
+
+        #[test]
+        fn success() {
+            let doc = indoc! {r#"
+            version: 0.0.1
+
+            source:
+              owner: my_name
+              name: test1
+              git_ref: main
+
+            destinations:
+              - owner: my_name
+                name: test2
+
+            token: random_token
+
+            transformations:
+              - fn: builtin.move
+                include: docs/**
+                args:
+                  before: docs
+                  after: guide
+              - fn: builtin.move
+                include: ["src/**", "lib/**"]
+                exclude: ["**/*.json"]
+                args:
+                  before: src
+                  after: vendor
+              - fn: builtin.move
+                exclude: README.md
+                args:
+                  before: ""
+                  after: vendor
+            "#};
+
+            let config = parse_config(read_config(doc).unwrap()).unwrap();
+
+            let paths: Vec<Option<WorkDirExpression>> = config
+                .transformations
+                .unwrap()
+                .into_iter()
+                .map(|transformation| match transformation {
+                    Transformation::Move { paths, .. } => paths.map(WorkDirExpression::Glob),
+                    _ => panic!("expected builtin.move"),
+                })
+                .collect();
+            assert_eq!(
+                paths,
+                vec![
+                    Some(create_glob(&["docs/**"], &[])),
+                    Some(create_glob(&["src/**", "lib/**"], &["**/*.json"])),
+                    Some(create_glob(&["**"], &["README.md"])),
+                ]
+            );
+        }
+
+        #[test]
+        fn invalid_paths() {
+            let doc = indoc! {r#"
+            version: 0.0.1
+
+            source:
+              owner: my_name
+              name: test1
+              git_ref: main
+
+            destinations:
+              - owner: my_name
+                name: test2
+
+            token: random_token
+
+            transformations:
+              - fn: builtin.replace
+                include:
+                  md: "**/*.md"
+                args:
+                  before: a
+                  after: b
+            "#};
+
+            let config = read_config(doc).unwrap();
+
+            assert_eq!(
+                parse_config(config).unwrap_err().to_string(),
+                "invalid config: builtin.replace.include should be a glob or a list of globs"
+            );
+        }
+    }
+
+    mod transformations_reverse {
+        use regex::Regex;
+
+        use crate::cli::parser::{reverse_transformations, CustomRegex};
+        use crate::fixtures::workdir_path::create_glob;
+
+        use super::*;
+
+        fn get_transformations(transformations: &str) -> Option<Vec<Transformation>> {
+            let doc = format!(
+                indoc! {r#"
+                version: 0.0.1
+
+                source:
+                  owner: my_name
+                  name: test1
+                  git_ref: main
+
+                destinations:
+                  - owner: my_name
+                    name: test2
+
+                token: random_token
+
+                transformations:
+                {}
+                "#},
+                transformations
+            );
+
+            parse_config(read_config(&doc).unwrap())
+                .unwrap()
+                .transformations
+        }
+
+        fn get_reverse_error(transformations: &str) -> String {
+            reverse_transformations(&get_transformations(transformations))
+                .unwrap_err()
+                .to_string()
+        }
+
+        #[test]
+        fn success() {
+            let transformations = get_transformations(indoc! {r#"
+                  - fn: builtin.move
+                    args:
+                      before: ""
+                      after: vendor
+                  - fn: builtin.replace
+                    include: vendor/**
+                    args:
+                      before: 'v1\.0 \(\$\)'
+                      after: v2.0 $$HOME
+            "#});
+
+            assert_eq!(
+                reverse_transformations(&transformations).unwrap(),
+                Some(vec![
+                    Transformation::Replace {
+                        args: ReplaceArgs {
+                            before: CustomRegex(Regex::new(r"v2\.0 \$HOME").unwrap()),
+                            after: "v1.0 ($$)".to_string(),
+                        },
+                        paths: match create_glob(&["vendor/**"], &[]) {
+                            WorkDirExpression::Glob(glob_expression) => Some(glob_expression),
+                            _ => unreachable!(),
+                        },
+                    },
+                    Transformation::Move {
+                        args: MoveArgs {
+                            before: "vendor".to_string(),
+                            after: "".to_string(),
+                        },
+                        paths: None,
+                    },
+                ])
+            );
+            assert_eq!(reverse_transformations(&None).unwrap(), None);
+        }
+
+        #[test]
+        fn lossy_replaces() {
+            assert_eq!(
+                get_reverse_error(indoc! {r#"
+                  - fn: builtin.replace
+                    args:
+                      before: 'v\d+'
+                      after: v2
+                "#}),
+                r"invalid config: builtin.replace `v\d+` can't be reversed, before should be literal text"
+            );
+            assert_eq!(
+                get_reverse_error(indoc! {r#"
+                  - fn: builtin.replace
+                    args:
+                      before: '(foo|bar)'
+                      after: baz
+                "#}),
+                "invalid config: builtin.replace `(foo|bar)` can't be reversed, before should be literal text"
+            );
+            assert_eq!(
+                get_reverse_error(indoc! {r#"
+                  - fn: builtin.replace
+                    args:
+                      before: foo
+                      after: $0-bar
+                "#}),
+                "invalid config: builtin.replace `foo` can't be reversed, after should be non-empty literal text"
+            );
+            assert_eq!(
+                get_reverse_error(indoc! {r#"
+                  - fn: builtin.replace
+                    args:
+                      before: foo
+                      after: ""
+                "#}),
+                "invalid config: builtin.replace `foo` can't be reversed, after should be non-empty literal text"
+            );
+        }
+
+        #[test]
+        fn not_reversible() {
+            assert_eq!(
+                get_reverse_error(indoc! {r#"
+                  - fn: builtin.move
+                    args:
+                      before: a
+                      after: b
+                  - fn: builtin.delete
+                    args:
+                      pattern: "**/*.tmp"
+                "#}),
+                "invalid config: builtin.delete can't be reversed, only builtin.move and builtin.replace can"
+            );
+            assert_eq!(
+                get_reverse_error(indoc! {r#"
+                  - fn: builtin.move
+                    include: docs/**
+                    args:
+                      before: docs
+                      after: guide
+                "#}),
+                "invalid config: builtin.move with include/exclude can't be reversed"
+            );
+        }
+
+        #[test]
+        fn reverse_config() {
+            let doc = indoc! {r#"
+            version: 0.0.1
+
+            source:
+              owner: my_name
+              name: test1
+              git_ref: main
+
+            destinations:
+              - owner: other
+                name: test2
+
+            token: random_token
+
+            origin_files: glob("shared/**")
+            destination_files: glob("vendor/**")
+
+            transformations:
+              - fn: builtin.move
+                args:
+                  before: shared
+                  after: vendor
+            "#};
+            let config = parse_config(read_config(doc).unwrap()).unwrap();
+
+            let reversed_config = config.reverse(&config.destinations[0], "develop").unwrap();
+
+            assert_eq!(
+                reversed_config.source,
+                SourceRepository {
+                    owner: "other".to_string(),
+                    name: "test2".to_string(),
+                    git_ref: "develop".to_string(),
+                    connection: Default::default(),
+                }
+            );
+            assert_eq!(
+                reversed_config.destinations,
+                vec![DestinationRepository {
+                    owner: "my_name".to_string(),
+                    name: "test1".to_string(),
+                    git_ref: Some("main".to_string()),
+                    connection: Default::default(),
+                    overrides: Default::default(),
+                }]
+            );
+            assert_eq!(reversed_config.origin_files, config.destination_files);
+            assert_eq!(reversed_config.destination_files, config.origin_files);
+            assert_eq!(
+                reversed_config.transformations,
+                Some(vec![Transformation::Move {
+                    args: MoveArgs {
+                        before: "vendor".to_string(),
+                        after: "shared".to_string(),
+                    },
+                    paths: None,
+                }])
+            );
+        }
+    }
+
+    mod connections {
+        use crate::cli::parser::{Connection, DEFAULT_WEB_URL};
+
+        use super::*;
+
+        #[test]
+        fn repository_overrides() {
+            let doc = indoc! {r#"
+            version: 0.0.1
+
+            source:
+              owner: my_name
+              name: test1
+              git_ref: main
+
+            destinations:
+              - owner: corp
+                name: test2
+                api_url: https://github.corp.com/api/v3/
+                token: ghes_token
+              - owner: corp
+                name: test3
+                web_url: https://mirror.corp.com
+
+            token: random_token
+            "#};
+
+            let config = parse_config(read_config(doc).unwrap()).unwrap();
+
+            assert_eq!(
+                config.get_connection(&config.source.connection),
+                Connection {
+                    api_url: None,
+                    web_url: DEFAULT_WEB_URL.to_string(),
+                    credentials: Credentials::Token(Token::from("random_token")),
+                }
+            );
+            assert_eq!(
+                config.get_connection(&config.destinations[0].connection),
+                Connection {
+                    api_url: Some("https://github.corp.com/api/v3/".to_string()),
+                    web_url: "https://github.corp.com".to_string(),
+                    credentials: Credentials::Token(Token::from("ghes_token")),
+                }
+            );
+            assert_eq!(
+                config.get_connection(&config.destinations[1].connection),
+                Connection {
+                    api_url: None,
+                    web_url: "https://mirror.corp.com".to_string(),
+                    credentials: Credentials::Token(Token::from("random_token")),
+                }
+            );
+            assert_eq!(config.redact("random_token and ghes_token"), "*** and ***");
+        }
+
+        #[test]
+        fn top_level_api_url() {
+            let doc = indoc! {r#"
+            version: 0.0.1
+
+            source:
+              owner: my_name
+              name: test1
+              git_ref: main
+              api_url: https://api.github.com
+
+            destinations:
+              - owner: corp
+                name: test2
+
+            api_url: https://github.corp.com/api/v3
+            token: random_token
+            "#};
+
+            let config = parse_config(read_config(doc).unwrap()).unwrap();
+
+            assert_eq!(
+                config.get_connection(&config.source.connection).web_url,
+                DEFAULT_WEB_URL
+            );
+            assert_eq!(
+                config.get_connection(&config.destinations[0].connection),
+                Connection {
+                    api_url: Some("https://github.corp.com/api/v3".to_string()),
+                    web_url: "https://github.corp.com".to_string(),
+                    credentials: Credentials::Token(Token::from("random_token")),
+                }
+            );
+        }
+
+        #[test]
+        fn conflicting_repository_credentials() {
+            let doc = indoc! {r#"
+            version: 0.0.1
+
+            source:
+              owner: my_name
+              name: test1
+              git_ref: main
+              token: a
+              app:
+                id: 1
+                private_key_path: key.pem
+
+            destinations:
+              - owner: corp
+                name: test2
+
+            token: random_token
+            "#};
+
+            let config = read_config(doc).unwrap();
+
+            assert_eq!(
+                parse_config(config).unwrap_err().to_string(),
+                "invalid config: only one of token, token_file, token_command and app should be set"
+            );
+        }
+    }
+
+    mod destination_overrides {
+        use crate::cli::common::PullRequestConfig;
+
+        use super::*;
+
+        const DOC: &str = indoc! {r#"
+        version: 0.0.1
+
+        source:
+          owner: my_name
+          name: test1
+          git_ref: main
+
+        destinations:
+          - owner: my_name
+            name: test2
+          - owner: my_name
+            name: test3
+            destination_files: glob("vendor/shared/**")
+            transformations:
+              - fn: builtin.move
+                args:
+                  before: ""
+                  after: vendor/shared
+            pull_request:
+              draft: true
+              labels: [vendored]
+          - owner: my_name
+            name: test4
+            transformations: []
+
+        token: random_token
+
+        origin_files: glob("**")
+
+        destination_files: glob("shared/**")
+
+        transformations:
+          - fn: builtin.move
+            args:
+              before: ""
+              after: shared
+
+        pull_request:
+          title: Sync shared code
+          labels: [sync]
+        "#};
+
+        #[test]
+        fn inherits_top_level_settings() {
+            let config = parse_config(read_config(DOC).unwrap()).unwrap();
+
+            assert_eq!(
+                config.for_destination(&config.destinations[0]).unwrap(),
+                config
+            );
+        }
+
+        #[test]
+        fn overrides_top_level_settings() {
+            let config = parse_config(read_config(DOC).unwrap()).unwrap();
+
+            let destination_config = config.for_destination(&config.destinations[1]).unwrap();
+            assert_eq!(
+                destination_config.destination_files,
+                create_glob_single("vendor/shared/**")
+            );
+            assert_eq!(
+                destination_config.transformations,
+                Some(vec![Transformation::Move {
+                    args: MoveArgs {
+                        before: "".to_string(),
+                        after: "vendor/shared".to_string(),
+                    },
+                    paths: None,
+                }])
+            );
+            assert_eq!(
+                destination_config.pull_request,
+                PullRequestConfig {
+                    title: Some("Sync shared code".to_string()),
+                    draft: Some(true),
+                    labels: Some(vec!["vendored".to_string()]),
+                }
+            );
+
+            let destination_config = config.for_destination(&config.destinations[2]).unwrap();
+            assert_eq!(destination_config.transformations, Some(vec![]));
+            assert_eq!(
+                destination_config.destination_files,
+                create_glob_single("shared/**")
+            );
+        }
+
+        #[test]
+        fn invalid_override() {
+            let doc = DOC.replace(r#"glob("vendor/shared/**")"#, r#"glob("***")"#);
+
+            assert!(matches!(
+                parse_config(read_config(&doc).unwrap()).unwrap_err(),
+                Error::Glob { pattern, .. } if pattern == "***"
+            ));
+        }
+
+        #[test]
+        fn duplicate_destination() {
+            let doc = DOC.replace("name: test4", "name: Test2");
+
+            assert_eq!(
+                parse_config(read_config(&doc).unwrap())
+                    .unwrap_err()
+                    .to_string(),
+                "invalid config: destination my_name/Test2 is listed twice"
+            );
         }
     }
 }
@@ -1,32 +1,300 @@
-use anyhow::Result;
-use serde::Deserialize;
+use serde::de::value::{MapAccessDeserializer, SeqAccessDeserializer};
+use serde::de::{self, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer};
 use serde_yaml;
+use std::fmt;
 
-use super::common::{DestinationRepository, SourceRepository};
+use super::common::{
+    ConnectionConfig, CredentialsConfig, DestinationRepository, PullRequestConfig, SourceRepository,
+};
+use crate::error::{Error, Result};
 
 #[derive(Clone, Debug, PartialEq, Deserialize)]
 pub struct Config {
     pub version: String,
     pub source: SourceRepository,
     pub destinations: Vec<DestinationRepository>,
-    pub token: String,
-    pub destination_files: Option<String>,
-    pub origin_files: Option<String>,
+    /// Defaults of every repository
+    #[serde(flatten)]
+    pub connection: ConnectionConfig,
+    pub destination_files: Option<WorkDirConfig>,
+    pub origin_files: Option<WorkDirConfig>,
     pub transformations: Option<Vec<serde_json::Value>>,
     pub update_fns: Option<Vec<serde_json::Value>>,
+    pub max_concurrency: Option<usize>,
+    #[serde(default)]
+    pub pull_request: PullRequestConfig,
 }
 
-pub fn read_config(config: &str) -> Result<Config, Box<dyn std::error::Error>> {
-    let deserialized_config: serde_yaml::Result<Config> = serde_yaml::from_str(&config);
+/// `origin_files`/`destination_files` are either a path, a `glob(...)`
+/// expression or lists of glob patterns:
+///
+/// ```yaml
+/// origin_files:
+///   include: ["src/**", "docs/**"]
+///   exclude: ["**/*.tmp"]
+/// ```
+///
+/// Each list may also be a single glob.
+#[derive(Clone, Debug, PartialEq)]
+pub enum WorkDirConfig {
+    Expression(String),
+    Glob {
+        include: Vec<String>,
+        exclude: Vec<String>,
+    },
+}
+
+/// Unknown keys are rejected, a misspelled `exclude` would otherwise sync
+/// everything it was meant to leave out.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct WorkDirGlobConfig {
+    include: Patterns,
+    #[serde(default)]
+    exclude: Patterns,
+}
+
+/// A single glob or a list of globs.
+#[derive(Default)]
+struct Patterns(Vec<String>);
+
+impl<'de> Deserialize<'de> for WorkDirConfig {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        struct WorkDirVisitor;
+
+        impl<'de> Visitor<'de> for WorkDirVisitor {
+            type Value = WorkDirConfig;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a path, a glob(...) expression or include and exclude globs")
+            }
+
+            fn visit_str<E: de::Error>(
+                self,
+                expression: &str,
+            ) -> std::result::Result<Self::Value, E> {
+                Ok(WorkDirConfig::Expression(expression.to_string()))
+            }
+
+            fn visit_map<A: MapAccess<'de>>(
+                self,
+                map: A,
+            ) -> std::result::Result<Self::Value, A::Error> {
+                let glob = WorkDirGlobConfig::deserialize(MapAccessDeserializer::new(map))?;
+
+                Ok(WorkDirConfig::Glob {
+                    include: glob.include.0,
+                    exclude: glob.exclude.0,
+                })
+            }
+        }
+
+        deserializer.deserialize_any(WorkDirVisitor)
+    }
+}
+
+impl<'de> Deserialize<'de> for Patterns {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        struct PatternsVisitor;
+
+        impl<'de> Visitor<'de> for PatternsVisitor {
+            type Value = Patterns;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a glob or a list of globs")
+            }
+
+            fn visit_str<E: de::Error>(self, pattern: &str) -> std::result::Result<Self::Value, E> {
+                Ok(Patterns(vec![pattern.to_string()]))
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(
+                self,
+                seq: A,
+            ) -> std::result::Result<Self::Value, A::Error> {
+                Ok(Patterns(Vec::deserialize(SeqAccessDeserializer::new(seq))?))
+            }
+        }
+
+        deserializer.deserialize_any(PatternsVisitor)
+    }
+}
+
+/// A config file syncing several sources, one per job. The top-level
+/// settings are the defaults of every job:
+///
+/// ```yaml
+/// version: 0.0.1
+/// token_command: gh auth token
+///
+/// jobs:
+///   - name: shared
+///     source: ...
+///     destinations: ...
+///   - name: docs
+///     source: ...
+///     destinations: ...
+/// ```
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct JobsConfig {
+    pub version: String,
+    #[serde(flatten)]
+    pub connection: ConnectionConfig,
+    pub max_concurrency: Option<usize>,
+    #[serde(default)]
+    pub pull_request: PullRequestConfig,
+    pub jobs: Vec<JobConfig>,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct JobConfig {
+    /// Selects the job with `--job`, only a config without `jobs` has an unnamed job
+    pub name: Option<String>,
+    pub source: SourceRepository,
+    pub destinations: Vec<DestinationRepository>,
+    /// Overrides the top-level `api_url`, `web_url` and credentials
+    #[serde(flatten)]
+    pub connection: ConnectionConfig,
+    pub destination_files: Option<WorkDirConfig>,
+    pub origin_files: Option<WorkDirConfig>,
+    pub transformations: Option<Vec<serde_json::Value>>,
+    pub update_fns: Option<Vec<serde_json::Value>>,
+    pub max_concurrency: Option<usize>,
+    /// Overrides single settings of the top-level `pull_request`
+    #[serde(default)]
+    pub pull_request: PullRequestConfig,
+}
+
+impl JobsConfig {
+    /// Keeps the jobs named in `names`, or every job when `names` is empty.
+    pub fn select(&mut self, names: &[String]) -> Result<()> {
+        if let Some(name) = names
+            .iter()
+            .find(|name| !self.jobs.iter().any(|job| job.name.as_ref() == Some(name)))
+        {
+            return Err(Error::Config(format!("unknown job `{}`", name)));
+        }
 
-    let result = match deserialized_config {
-        Ok(content) => content,
-        Err(error) => {
-            return Err(error.into());
+        if !names.is_empty() {
+            self.jobs
+                .retain(|job| job.name.as_ref().is_some_and(|name| names.contains(name)));
         }
-    };
 
-    Ok(result)
+        Ok(())
+    }
+
+    /// Returns the config of every job, the settings a job doesn't set taken
+    /// from the top level. Credentials are taken as a whole, so a job setting
+    /// `token_file` doesn't also get the top-level `token`.
+    pub fn into_configs(self) -> Vec<(Option<String>, Config)> {
+        let defaults = self.connection;
+
+        self.jobs
+            .into_iter()
+            .map(|job| {
+                let credentials = if job.connection.credentials == CredentialsConfig::default() {
+                    defaults.credentials.clone()
+                } else {
+                    job.connection.credentials
+                };
+
+                let config = Config {
+                    version: self.version.clone(),
+                    source: job.source,
+                    destinations: job.destinations,
+                    connection: ConnectionConfig {
+                        api_url: job.connection.api_url.or_else(|| defaults.api_url.clone()),
+                        web_url: job.connection.web_url.or_else(|| defaults.web_url.clone()),
+                        credentials,
+                    },
+                    destination_files: job.destination_files,
+                    origin_files: job.origin_files,
+                    transformations: job.transformations,
+                    update_fns: job.update_fns,
+                    max_concurrency: job.max_concurrency.or(self.max_concurrency),
+                    pull_request: self.pull_request.merge(&job.pull_request),
+                };
+
+                (job.name, config)
+            })
+            .collect()
+    }
+}
+
+impl From<Config> for JobsConfig {
+    /// A config without `jobs` is a single unnamed job.
+    fn from(config: Config) -> Self {
+        JobsConfig {
+            version: config.version,
+            connection: config.connection,
+            max_concurrency: config.max_concurrency,
+            pull_request: config.pull_request,
+            jobs: vec![JobConfig {
+                name: None,
+                source: config.source,
+                destinations: config.destinations,
+                connection: Default::default(),
+                destination_files: config.destination_files,
+                origin_files: config.origin_files,
+                transformations: config.transformations,
+                update_fns: config.update_fns,
+                max_concurrency: None,
+                pull_request: Default::default(),
+            }],
+        }
+    }
+}
+
+pub fn read_config(config: &str) -> Result<Config> {
+    let deserialized_config: Config = serde_yaml::from_str(config)?;
+
+    Ok(deserialized_config)
+}
+
+/// Reads a config file with a `jobs` list, or a single job at the top level.
+pub fn read_jobs(config: &str) -> Result<JobsConfig> {
+    let value: serde_yaml::Value = serde_yaml::from_str(config)?;
+    if value.get("jobs").is_none() {
+        return Ok(serde_yaml::from_value::<Config>(value)?.into());
+    }
+
+    let jobs_config: JobsConfig = serde_yaml::from_value(value)?;
+
+    let mut names = Vec::new();
+    for job in jobs_config.jobs.iter() {
+        let name = job
+            .name
+            .as_ref()
+            .ok_or_else(|| Error::Config("every job should have a name".to_string()))?;
+        if names.contains(&name) {
+            return Err(Error::Config(format!("job `{}` is defined twice", name)));
+        }
+        if !is_ref_component(name) {
+            return Err(Error::Config(format!(
+                "job `{}` should be usable in a branch name, e.g. without spaces, `/` or `..`",
+                name
+            )));
+        }
+        names.push(name);
+    }
+
+    Ok(jobs_config)
+}
+
+/// The job name is a component of the syncy branch, so it follows the rules
+/// of `git check-ref-format` for a single component.
+fn is_ref_component(name: &str) -> bool {
+    !name.is_empty()
+        && name != "@"
+        && !name.starts_with('.')
+        && !name.ends_with('.')
+        && !name.ends_with(".lock")
+        && !name.contains("..")
+        && !name.contains("@{")
+        && !name
+            .chars()
+            .any(|c| c.is_ascii_control() || " ~^:?*[\\/".contains(c))
 }
 
 #[cfg(test)]
@@ -34,7 +302,13 @@ mod tests {
 
     mod reader {
 
-        use super::super::{read_config, Config, DestinationRepository, SourceRepository};
+        use super::super::{
+            read_config, Config, DestinationRepository, SourceRepository, WorkDirConfig,
+        };
+        use crate::cli::{
+            common::{ConnectionConfig, CredentialsConfig},
+            Token,
+        };
         use indoc::indoc;
         use serde_json::json;
 
@@ -65,17 +339,21 @@ mod tests {
                   after: my_folder  
             "#};
 
-            let parsed_config = read_config(&doc).unwrap();
+            let parsed_config = read_config(doc).unwrap();
 
             let expected_source = SourceRepository {
                 owner: "my_name".to_string(),
                 name: "test1".to_string(),
                 git_ref: "main".to_string(),
+                connection: Default::default(),
             };
 
             let expected_destination = DestinationRepository {
                 owner: "my_name".to_string(),
                 name: "test2".to_string(),
+                git_ref: None,
+                connection: Default::default(),
+                overrides: Default::default(),
             };
 
             let expected_transformation_args = json!({
@@ -90,11 +368,21 @@ mod tests {
                 version: "0.0.1".to_string(),
                 source: expected_source,
                 destinations: vec![expected_destination],
-                token: "random_token".to_string(),
-                origin_files: Some("glob(\"**\")".to_string()),
-                destination_files: Some("glob(\"my_folder/**\")".to_string()),
+                connection: ConnectionConfig {
+                    credentials: CredentialsConfig {
+                        token: Some(Token::from("random_token")),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                origin_files: Some(WorkDirConfig::Expression("glob(\"**\")".to_string())),
+                destination_files: Some(WorkDirConfig::Expression(
+                    "glob(\"my_folder/**\")".to_string(),
+                )),
                 transformations: Some(vec![expected_transformation]),
                 update_fns: None,
+                max_concurrency: None,
+                pull_request: Default::default(),
             };
 
             assert_eq!(parsed_config, expected_config);
@@ -128,28 +416,42 @@ mod tests {
 
             "#};
 
-            let parsed_config = read_config(&doc).unwrap();
+            let parsed_config = read_config(doc).unwrap();
 
             let expected_source = SourceRepository {
                 owner: "my_name".to_string(),
                 name: "test1".to_string(),
                 git_ref: "main".to_string(),
+                connection: Default::default(),
             };
 
             let expected_destination = DestinationRepository {
                 owner: "my_name".to_string(),
                 name: "test2".to_string(),
+                git_ref: None,
+                connection: Default::default(),
+                overrides: Default::default(),
             };
 
             let expected_config = Config {
                 version: "0.0.1".to_string(),
                 source: expected_source,
                 destinations: vec![expected_destination],
-                token: "random_token".to_string(),
-                origin_files: Some("glob(\"**\")".to_string()),
-                destination_files: Some("glob(\"my_folder/**\")".to_string()),
+                connection: ConnectionConfig {
+                    credentials: CredentialsConfig {
+                        token: Some(Token::from("random_token")),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                origin_files: Some(WorkDirConfig::Expression("glob(\"**\")".to_string())),
+                destination_files: Some(WorkDirConfig::Expression(
+                    "glob(\"my_folder/**\")".to_string(),
+                )),
                 transformations: None,
                 update_fns: None,
+                max_concurrency: None,
+                pull_request: Default::default(),
             };
 
             assert_eq!(parsed_config, expected_config);
@@ -182,17 +484,21 @@ mod tests {
                   after: my_folder  
             "#};
 
-            let parsed_config = read_config(&doc).unwrap();
+            let parsed_config = read_config(doc).unwrap();
 
             let expected_source = SourceRepository {
                 owner: "my_name".to_string(),
                 name: "test1".to_string(),
                 git_ref: "main".to_string(),
+                connection: Default::default(),
             };
 
             let expected_destination = DestinationRepository {
                 owner: "my_name".to_string(),
                 name: "test2".to_string(),
+                git_ref: None,
+                connection: Default::default(),
+                overrides: Default::default(),
             };
 
             let expected_transformation_args = json!({
@@ -207,14 +513,223 @@ mod tests {
                 version: "0.0.1".to_string(),
                 source: expected_source,
                 destinations: vec![expected_destination],
-                token: "random_token".to_string(),
-                origin_files: Some("path".to_string()),
-                destination_files: Some("another_path".to_string()),
+                connection: ConnectionConfig {
+                    credentials: CredentialsConfig {
+                        token: Some(Token::from("random_token")),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                origin_files: Some(WorkDirConfig::Expression("path".to_string())),
+                destination_files: Some(WorkDirConfig::Expression("another_path".to_string())),
                 transformations: Some(vec![expected_transformation]),
                 update_fns: None,
+                max_concurrency: None,
+                pull_request: Default::default(),
             };
 
             assert_eq!(parsed_config, expected_config);
         }
+
+        const GLOBS_DOC: &str = indoc! {r#"
+        version: 0.0.1
+
+        source:
+          owner: my_name
+          name: test1
+          git_ref: main
+
+        destinations:
+          - owner: my_name
+            name: test2
+
+        token: random_token
+
+        destination_files:
+          include: src/**
+          exclude: ["src/dist/**"]
+        "#};
+
+        #[test]
+        fn work_dir_globs() {
+            let parsed_config = read_config(GLOBS_DOC).unwrap();
+
+            assert_eq!(
+                parsed_config.destination_files,
+                Some(WorkDirConfig::Glob {
+                    include: vec!["src/**".to_string()],
+                    exclude: vec!["src/dist/**".to_string()],
+                })
+            );
+        }
+
+        #[test]
+        fn invalid_work_dir_globs() {
+            let error = read_config(&GLOBS_DOC.replace("exclude:", "exlude:")).unwrap_err();
+            assert!(error.to_string().starts_with(
+                "invalid config: destination_files: unknown field `exlude`, expected `include` or `exclude`"
+            ));
+
+            let error =
+                read_config(&GLOBS_DOC.replace("include: src/**", "include: 1")).unwrap_err();
+            assert!(error.to_string().starts_with(
+                "invalid config: destination_files.include: invalid type: integer `1`, expected a glob or a list of globs"
+            ));
+
+            let error = read_config(&GLOBS_DOC.replace("  include: src/**\n", "")).unwrap_err();
+            assert!(error
+                .to_string()
+                .starts_with("invalid config: destination_files: missing field `include`"));
+        }
+    }
+
+    mod jobs {
+        use super::super::{read_jobs, JobsConfig};
+        use crate::cli::{common::PullRequestConfig, Token};
+        use indoc::indoc;
+
+        const DOC: &str = indoc! {r#"
+        version: 0.0.1
+        token: random_token
+        api_url: https://github.corp.com/api/v3
+        max_concurrency: 4
+        pull_request:
+          labels: [sync]
+
+        jobs:
+          - name: shared
+            source:
+              owner: my_name
+              name: test1
+              git_ref: main
+            destinations:
+              - owner: my_name
+                name: test2
+            origin_files: glob("shared/**")
+          - name: docs
+            token_file: ghes_token
+            max_concurrency: 2
+            pull_request:
+              draft: true
+            source:
+              owner: my_name
+              name: docs
+              git_ref: main
+            destinations:
+              - owner: my_name
+                name: test3
+        "#};
+
+        #[test]
+        fn job_defaults() {
+            let configs = read_jobs(DOC).unwrap().into_configs();
+
+            let (name, config) = &configs[0];
+            assert_eq!(name.as_deref(), Some("shared"));
+            assert_eq!(config.source.name, "test1");
+            assert_eq!(
+                config.connection.credentials.token,
+                Some(Token::from("random_token"))
+            );
+            assert_eq!(
+                config.connection.api_url.as_deref(),
+                Some("https://github.corp.com/api/v3")
+            );
+            assert_eq!(config.max_concurrency, Some(4));
+
+            let (name, config) = &configs[1];
+            assert_eq!(name.as_deref(), Some("docs"));
+            assert_eq!(config.connection.credentials.token, None);
+            assert_eq!(
+                config.connection.credentials.token_file.as_deref(),
+                Some("ghes_token")
+            );
+            assert_eq!(config.max_concurrency, Some(2));
+            assert_eq!(
+                config.pull_request,
+                PullRequestConfig {
+                    title: None,
+                    draft: Some(true),
+                    labels: Some(vec!["sync".to_string()]),
+                }
+            );
+        }
+
+        #[test]
+        fn single_job() {
+            let doc = indoc! {r#"
+            version: 0.0.1
+
+            source:
+              owner: my_name
+              name: test1
+              git_ref: main
+
+            destinations:
+              - owner: my_name
+                name: test2
+
+            token: random_token
+            "#};
+
+            let config: JobsConfig = read_jobs(doc).unwrap();
+
+            assert_eq!(config.jobs.len(), 1);
+            assert_eq!(config.jobs[0].name, None);
+            assert_eq!(
+                config.connection.credentials.token,
+                Some(Token::from("random_token"))
+            );
+        }
+
+        #[test]
+        fn select_jobs() {
+            let mut config = read_jobs(DOC).unwrap();
+            config.select(&["docs".to_string()]).unwrap();
+            assert_eq!(config.jobs.len(), 1);
+            assert_eq!(config.jobs[0].name.as_deref(), Some("docs"));
+
+            let mut config = read_jobs(DOC).unwrap();
+            config.select(&[]).unwrap();
+            assert_eq!(config.jobs.len(), 2);
+
+            assert_eq!(
+                read_jobs(DOC)
+                    .unwrap()
+                    .select(&["unknown".to_string()])
+                    .unwrap_err()
+                    .to_string(),
+                "invalid config: unknown job `unknown`"
+            );
+        }
+
+        #[test]
+        fn invalid_job_names() {
+            assert_eq!(
+                read_jobs(&DOC.replace("- name: docs\n    token_file", "- token_file"))
+                    .unwrap_err()
+                    .to_string(),
+                "invalid config: every job should have a name"
+            );
+            assert_eq!(
+                read_jobs(&DOC.replace("- name: docs", "- name: shared"))
+                    .unwrap_err()
+                    .to_string(),
+                "invalid config: job `shared` is defined twice"
+            );
+
+            for name in ["docs sync", "a..b", "docs/sync", ".docs", "docs.lock", "@"] {
+                assert_eq!(
+                    read_jobs(&DOC.replace("- name: docs", &format!("- name: \"{}\"", name)))
+                        .unwrap_err()
+                        .to_string(),
+                    format!(
+                        "invalid config: job `{}` should be usable in a branch name, e.g. without spaces, `/` or `..`",
+                        name
+                    )
+                );
+            }
+            assert!(read_jobs(&DOC.replace("- name: docs", "- name: docs-v1.2_sync")).is_ok());
+        }
     }
 }
@@ -0,0 +1,33 @@
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("invalid config: {0}")]
+    Config(String),
+
+    #[error("invalid glob `{pattern}`: {source}")]
+    Glob {
+        pattern: String,
+        source: glob::PatternError,
+    },
+
+    #[error("invalid regex: {0}")]
+    Regex(#[from] regex::Error),
+
+    #[error("github request failed: {0}")]
+    Http(#[from] octocrab::Error),
+
+    #[error("unexpected provider response: {0}")]
+    Provider(String),
+
+    #[error("conflict: {0}")]
+    Conflict(String),
+}
+
+impl From<serde_yaml::Error> for Error {
+    fn from(error: serde_yaml::Error) -> Self {
+        Error::Config(error.to_string())
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
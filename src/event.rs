@@ -1,12 +1,18 @@
+use std::fmt;
+
+use crate::git_tree::NodeKind;
+
 #[derive(Debug, PartialEq, PartialOrd, Eq, Ord)]
 pub enum Event {
     Create {
         path: String,
-        content: Option<String>,
+        content: Option<Vec<u8>>,
+        kind: NodeKind,
     },
     Update {
         path: String,
-        content: Option<String>,
+        content: Option<Vec<u8>>,
+        kind: NodeKind,
         sha: String,
     },
     Delete {
@@ -14,3 +20,23 @@ pub enum Event {
         sha: String,
     },
 }
+
+impl Event {
+    pub fn path(&self) -> &str {
+        match self {
+            Event::Create { path, .. }
+            | Event::Update { path, .. }
+            | Event::Delete { path, .. } => path,
+        }
+    }
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Event::Create { path, .. } => write!(f, "+ {}", path),
+            Event::Update { path, .. } => write!(f, "~ {}", path),
+            Event::Delete { path, .. } => write!(f, "- {}", path),
+        }
+    }
+}
@@ -1,24 +1,45 @@
-use base64;
-use octocrab::models::repos::{Content, ContentLinks};
-use reqwest::Url;
+use serde_json::json;
 
-pub fn get_content_json(path: &str, name: &str, content: Option<&str>, r#type: &str) -> Content {
-    Content {
-        path: path.to_string(),
-        name: name.to_string(),
-        sha: "".to_string(),
-        content: Some(base64::encode(content.unwrap_or("").to_string())),
-        size: 45,
-        url: "".to_string(),
-        html_url: "".to_string(),
-        git_url: "".to_string(),
-        download_url: Some("".to_string()),
-        r#type: r#type.to_string(),
-        links: ContentLinks {
-            git: Url::parse("https://example.net").unwrap(),
-            html: Url::parse("https://example.net").unwrap(),
-            _self: Url::parse("https://example.net").unwrap(),
-        },
-        license: None,
-    }
+pub fn get_tree_entry_json(path: &str, sha: &str) -> serde_json::Value {
+    json!({
+        "path": path,
+        "mode": "100644",
+        "type": "blob",
+        "sha": sha,
+        "size": 45,
+        "url": format!("https://example.net/git/blobs/{}", sha),
+    })
+}
+
+pub fn get_tree_entry_with_mode_json(path: &str, sha: &str, mode: &str) -> serde_json::Value {
+    let r#type = if mode == "160000" { "commit" } else { "blob" };
+
+    json!({
+        "path": path,
+        "mode": mode,
+        "type": r#type,
+        "sha": sha,
+        "url": format!("https://example.net/git/blobs/{}", sha),
+    })
+}
+
+pub fn get_tree_folder_json(path: &str, sha: &str) -> serde_json::Value {
+    json!({
+        "path": path,
+        "mode": "040000",
+        "type": "tree",
+        "sha": sha,
+        "url": format!("https://example.net/git/trees/{}", sha),
+    })
+}
+
+pub fn get_blob_json(sha: &str, content: impl AsRef<[u8]>) -> serde_json::Value {
+    let content = content.as_ref();
+
+    json!({
+        "sha": sha,
+        "size": content.len(),
+        "content": base64::encode(content),
+        "encoding": "base64",
+    })
 }
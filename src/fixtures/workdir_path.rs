@@ -3,17 +3,33 @@ use crate::cli::{GlobExpression, WorkDirExpression};
 pub fn create_glob_single(val: &str) -> WorkDirExpression {
     let glob_pattern = glob::Pattern::new(val).unwrap();
 
-    WorkDirExpression::Glob(GlobExpression::Single(glob_pattern))
+    WorkDirExpression::Glob(GlobExpression {
+        include: vec![glob_pattern],
+        exclude: vec![],
+    })
 }
 
 pub fn create_glob_single_with_exclude(val: &str, exclude: &str) -> WorkDirExpression {
     let glob_pattern = glob::Pattern::new(val).unwrap();
     let glob_pattern_exclude = glob::Pattern::new(exclude).unwrap();
 
-    WorkDirExpression::Glob(GlobExpression::SingleWithExclude(
-        glob_pattern,
-        glob_pattern_exclude,
-    ))
+    WorkDirExpression::Glob(GlobExpression {
+        include: vec![glob_pattern],
+        exclude: vec![glob_pattern_exclude],
+    })
+}
+
+pub fn create_glob(include: &[&str], exclude: &[&str]) -> WorkDirExpression {
+    WorkDirExpression::Glob(GlobExpression {
+        include: include
+            .iter()
+            .map(|pattern| glob::Pattern::new(pattern).unwrap())
+            .collect(),
+        exclude: exclude
+            .iter()
+            .map(|pattern| glob::Pattern::new(pattern).unwrap())
+            .collect(),
+    })
 }
 
 pub fn create_workdir_path(val: &str) -> WorkDirExpression {
@@ -1,26 +1,199 @@
 use std::collections::HashMap;
 
+use regex::Regex;
+
 use crate::{
-    cli::{GlobExpression, Transformation, WorkDirExpression},
+    cli::{Transformation, UpdateFn, WorkDirExpression},
     event::Event,
 };
 
-#[derive(Debug, PartialEq)]
+/// The kind of a git tree entry, which decides the mode it's written with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum NodeKind {
+    Blob,
+    Executable,
+    /// A blob whose content is the path the link points to.
+    Symlink,
+    /// A submodule, its content is the sha of the commit it points to.
+    Gitlink,
+}
+
+impl NodeKind {
+    pub fn from_mode(mode: &str) -> Option<NodeKind> {
+        match mode {
+            "100644" => Some(NodeKind::Blob),
+            "100755" => Some(NodeKind::Executable),
+            "120000" => Some(NodeKind::Symlink),
+            "160000" => Some(NodeKind::Gitlink),
+            _ => None,
+        }
+    }
+
+    pub fn mode(&self) -> &'static str {
+        match self {
+            NodeKind::Blob => "100644",
+            NodeKind::Executable => "100755",
+            NodeKind::Symlink => "120000",
+            NodeKind::Gitlink => "160000",
+        }
+    }
+
+    /// The type of the git object the entry refers to.
+    pub fn object_type(&self) -> &'static str {
+        match self {
+            NodeKind::Gitlink => "commit",
+            _ => "blob",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct Node {
     pub path: String,
-    pub content: Option<String>,
+    pub content: Option<Vec<u8>>,
+    pub kind: NodeKind,
     pub git_url: String,
     pub sha: String,
 }
 
+impl Node {
+    /// Returns the content of regular files as text, symlinks, submodules and
+    /// binary files are never transformed.
+    pub fn text_content(&self) -> Option<&str> {
+        match self.kind {
+            NodeKind::Blob | NodeKind::Executable => self.content.as_deref().and_then(as_text),
+            NodeKind::Symlink | NodeKind::Gitlink => None,
+        }
+    }
+
+    /// Returns the sha of the object the entry refers to once it is written.
+    pub fn object_sha(&self) -> String {
+        match self.kind {
+            NodeKind::Gitlink => {
+                String::from_utf8_lossy(self.content.as_deref().unwrap_or_default()).to_string()
+            }
+            _ => get_blob_sha(&self.content),
+        }
+    }
+}
+
 pub type Tree = HashMap<String, Node>;
 
+/// Computes the sha git assigns to a blob with the given content,
+/// i.e. `sha1("blob {len}\0{content}")`.
+pub fn get_blob_sha(content: &Option<Vec<u8>>) -> String {
+    let content = content.as_deref().unwrap_or_default();
+
+    let mut hasher = sha1_smol::Sha1::new();
+    hasher.update(format!("blob {}\0", content.len()).as_bytes());
+    hasher.update(content);
+
+    hasher.digest().to_string()
+}
+
+/// Number of leading bytes inspected by [`is_binary`], the same as git uses.
+const BINARY_PROBE_LENGTH: usize = 8000;
+
+/// Guesses whether the content is binary the way git does: it is binary when a
+/// NUL byte shows up in the first 8000 bytes. Content which is not valid UTF-8
+/// is treated as binary too, since text transformations can't be applied to it.
+pub fn is_binary(content: &[u8]) -> bool {
+    content
+        .iter()
+        .take(BINARY_PROBE_LENGTH)
+        .any(|byte| *byte == 0)
+        || std::str::from_utf8(content).is_err()
+}
+
+/// Returns the content as text, or `None` when it is binary.
+pub fn as_text(content: &[u8]) -> Option<&str> {
+    if is_binary(content) {
+        return None;
+    }
+
+    std::str::from_utf8(content).ok()
+}
+
 pub trait GitTree {
     fn transform_tree(self, origin_files_glob: &WorkDirExpression, root_path: &str) -> Tree;
 
     fn generate_events(&self, destination_tree: &Tree) -> Vec<Event>;
 
-    fn apply_transformations(self, transformations: &Option<Vec<Transformation>>) -> Tree;
+    /// Applies the transformations to every node. `variables` are available
+    /// to `builtin.template`, together with the `path` of each file.
+    fn apply_transformations(
+        self,
+        transformations: &Option<Vec<Transformation>>,
+        variables: &HashMap<String, String>,
+    ) -> Tree;
+
+    /// Merges the source nodes into the files that already exist in the
+    /// destination tree: only the regions matched by the update fns are taken
+    /// from the source, the rest of the destination file is kept as is.
+    fn apply_update_fns(self, destination_tree: &Tree, update_fns: &Option<Vec<UpdateFn>>) -> Tree;
+}
+
+/// Matches `${name}` references in templates, spaces inside the braces are allowed.
+const TEMPLATE_VARIABLE: &str = r"\$\{\s*([A-Za-z0-9_.]+)\s*\}";
+
+/// Substitutes the variables referenced in `content`. Unknown variables are
+/// kept as is, so `${...}` of e.g. shell scripts survives the template.
+fn render_template(
+    template_regex: &Regex,
+    content: &str,
+    variables: &HashMap<String, String>,
+) -> String {
+    template_regex
+        .replace_all(content, |captures: &regex::Captures| {
+            match variables.get(&captures[1]) {
+                Some(value) => value.to_string(),
+                None => captures[0].to_string(),
+            }
+        })
+        .to_string()
+}
+
+/// Moves `path` from the `before` folder to the `after` folder, an empty
+/// folder stands for the repository root. Returns `None` when the path is
+/// outside of `before`.
+fn move_path(path: &str, before: &str, after: &str) -> Option<String> {
+    let relative_path = if before.is_empty() {
+        path
+    } else if path == before {
+        ""
+    } else {
+        path.strip_prefix(before)?.strip_prefix('/')?
+    };
+
+    let new_path = match (after.is_empty(), relative_path.is_empty()) {
+        (true, _) => relative_path.to_string(),
+        (false, true) => after.to_string(),
+        (false, false) => format!("{}/{}", after, relative_path),
+    };
+
+    Some(new_path)
+}
+
+/// Replaces the n-th match of `pattern` in `destination` with the n-th match in
+/// `source`. Matches without a counterpart in the source are left untouched.
+fn update_by_pattern(pattern: &Regex, source: &str, destination: &str) -> String {
+    let mut source_matches = pattern.find_iter(source);
+
+    let mut result = String::with_capacity(destination.len());
+    let mut last_end = 0;
+    for destination_match in pattern.find_iter(destination) {
+        let source_match = match source_matches.next() {
+            Some(source_match) => source_match,
+            None => break,
+        };
+
+        result.push_str(&destination[last_end..destination_match.start()]);
+        result.push_str(source_match.as_str());
+        last_end = destination_match.end();
+    }
+    result.push_str(&destination[last_end..]);
+
+    result
 }
 
 impl GitTree for HashMap<String, Node> {
@@ -31,30 +204,11 @@ impl GitTree for HashMap<String, Node> {
 
         let mut new_tree = Tree::new();
         for (key, node) in self {
-            match origin_files_glob {
-                WorkDirExpression::Glob(glob_expression) => match glob_expression {
-                    GlobExpression::Single(pattern) => {
-                        if pattern.matches(&key) {
-                            let new_val = key.trim_start_matches(&format!(
-                                "{root_path}/",
-                                root_path = root_path
-                            ));
-
-                            new_tree.insert(new_val.to_string(), node);
-                        }
-                    }
-                    GlobExpression::SingleWithExclude(include_pattern, exclude_pattern) => {
-                        if include_pattern.matches(&key) && !exclude_pattern.matches(&key) {
-                            let new_val = key.trim_start_matches(&format!(
-                                "{root_path}/",
-                                root_path = root_path
-                            ));
-
-                            new_tree.insert(new_val.to_string(), node);
-                        }
-                    }
-                },
-                _ => {}
+            if origin_files_glob.matches(&key) {
+                let new_val =
+                    key.trim_start_matches(&format!("{root_path}/", root_path = root_path));
+
+                new_tree.insert(new_val.to_string(), node);
             }
         }
 
@@ -66,14 +220,19 @@ impl GitTree for HashMap<String, Node> {
         for (source_key, source_node) in self.iter() {
             let destination_node = destination_tree.get(source_key);
             match destination_node {
+                Some(destination_node)
+                    if destination_node.kind == source_node.kind
+                        && destination_node.sha == source_node.object_sha() => {}
                 Some(destination_node) => events.push(Event::Update {
                     sha: destination_node.sha.to_string(),
                     path: source_node.path.to_string(),
                     content: source_node.content.clone(),
+                    kind: source_node.kind,
                 }),
                 None => events.push(Event::Create {
                     path: source_node.path.to_string(),
                     content: source_node.content.clone(),
+                    kind: source_node.kind,
                 }),
             }
         }
@@ -90,54 +249,116 @@ impl GitTree for HashMap<String, Node> {
         events
     }
 
-    fn apply_transformations(self, transformations: &Option<Vec<Transformation>>) -> Tree {
-        if let None = transformations {
-            return self;
-        }
+    fn apply_transformations(
+        self,
+        transformations: &Option<Vec<Transformation>>,
+        variables: &HashMap<String, String>,
+    ) -> Tree {
+        let transformations = match transformations {
+            Some(transformations) => transformations,
+            None => return self,
+        };
+        let template_regex = Regex::new(TEMPLATE_VARIABLE).expect("template regex is valid");
         let mut new_tree = Tree::new();
 
-        for (path, node) in self {
-            let mut new_path = "".to_string();
-            let mut new_content = "".to_string();
-            for t in transformations.as_ref().unwrap().iter() {
+        // every transformation works on the output of the previous one
+        'nodes: for (_, mut node) in self {
+            for t in transformations.iter() {
+                if !t.applies_to(&node.path) {
+                    continue;
+                }
+
                 match t {
-                    Transformation::Move { args } => {
-                        if path.starts_with(&args.before) {
-                            let trimmed_before_val = path.trim_start_matches(&format!(
-                                "{prefix_path}/",
-                                prefix_path = args.before
-                            ));
-
-                            new_path = format!(
-                                "{prefix}/{val}",
-                                val = trimmed_before_val,
-                                prefix = args.after
-                            );
+                    Transformation::Move { args, .. } => {
+                        if let Some(new_path) = move_path(&node.path, &args.before, &args.after) {
+                            node.path = new_path;
+                        }
+                    }
+                    Transformation::Replace { args, .. } => {
+                        if let Some(content_value) = node.text_content() {
+                            let new_content = args.before.0.replace_all(content_value, &args.after);
+                            node.content = Some(new_content.into_owned().into_bytes());
+                        }
+                    }
+                    Transformation::Rename { args, .. } => {
+                        let new_path = args.before.0.replace(&node.path, args.after.as_str());
+                        node.path = new_path.into_owned();
+                    }
+                    Transformation::Delete { args, .. } => {
+                        if args.pattern.matches(&node.path) {
+                            continue 'nodes;
+                        }
+                    }
+                    Transformation::Prepend { args, .. } => {
+                        if let Some(content_value) = node.text_content() {
+                            let new_content = format!("{}{}", args.text, content_value);
+                            node.content = Some(new_content.into_bytes());
                         }
                     }
-                    Transformation::Replace { args } => {
-                        let regex = &args.before.0;
-                        if let Some(content_value) = &node.content {
-                            let new_string = regex.replace_all(content_value, &args.after);
+                    Transformation::Append { args, .. } => {
+                        if let Some(content_value) = node.text_content() {
+                            let new_content = format!("{}{}", content_value, args.text);
+                            node.content = Some(new_content.into_bytes());
+                        }
+                    }
+                    Transformation::Template { args, .. } => {
+                        if let Some(content_value) = node.text_content() {
+                            let mut template_variables = variables.clone();
+                            template_variables.insert("path".to_string(), node.path.clone());
+                            template_variables.extend(args.variables.clone());
 
-                            new_content = new_string.to_string();
+                            let new_content = render_template(
+                                &template_regex,
+                                content_value,
+                                &template_variables,
+                            );
+                            node.content = Some(new_content.into_bytes());
                         }
                     }
                 };
             }
 
-            let result_content = if new_content.is_empty() {
-                node.content
-            } else {
-                Some(new_content)
-            };
-            let new_node = Node {
-                path: new_path.clone(),
-                content: result_content,
-                sha: node.sha,
-                git_url: node.git_url,
+            new_tree.insert(node.path.clone(), node);
+        }
+
+        new_tree
+    }
+
+    fn apply_update_fns(self, destination_tree: &Tree, update_fns: &Option<Vec<UpdateFn>>) -> Tree {
+        let update_fns = match update_fns {
+            Some(update_fns) => update_fns,
+            None => return self,
+        };
+
+        let mut new_tree = Tree::new();
+        for (path, node) in self {
+            let destination_content = destination_tree
+                .get(&path)
+                .and_then(|destination_node| destination_node.text_content());
+
+            let new_node = match (node.text_content(), destination_content) {
+                (Some(source_content), Some(destination_content)) => {
+                    let mut new_content = destination_content.to_string();
+                    for update_fn in update_fns.iter() {
+                        match update_fn {
+                            UpdateFn::UpdateByPattern { args } => {
+                                new_content = update_by_pattern(
+                                    &args.pattern.0,
+                                    source_content,
+                                    &new_content,
+                                );
+                            }
+                        }
+                    }
+
+                    Node {
+                        content: Some(new_content.into_bytes()),
+                        ..node
+                    }
+                }
+                _ => node,
             };
-            new_tree.insert(new_path, new_node);
+            new_tree.insert(path, new_node);
         }
 
         new_tree
@@ -147,8 +368,20 @@ impl GitTree for HashMap<String, Node> {
 #[cfg(test)]
 mod tests {
 
-    use super::{GitTree, Node, Tree};
-    use crate::fixtures::{content::get_content_json, workdir_path::create_glob_single};
+    use regex::Regex;
+
+    use std::collections::HashMap;
+
+    use super::{
+        get_blob_sha, is_binary, move_path, update_by_pattern, GitTree, Node, NodeKind, Tree,
+    };
+    use crate::cli::parser::{
+        reverse_transformations, AppendArgs, CustomRegex, DeleteArgs, GlobExpression, MoveArgs,
+        PrependArgs, RenameArgs, ReplaceArgs, TemplateArgs, Transformation, UpdateByPatternArgs,
+        UpdateFn,
+    };
+    use crate::event::Event;
+    use crate::fixtures::workdir_path::create_glob_single;
 
     #[test]
     fn test_success() {
@@ -158,7 +391,8 @@ mod tests {
             "folder/file1".to_string(),
             Node {
                 path: "folder/file1".to_string(),
-                content: Some("".to_string()),
+                content: Some(b"".to_vec()),
+                kind: NodeKind::Blob,
                 git_url: "".to_string(),
                 sha: "x132".to_string(),
             },
@@ -167,7 +401,8 @@ mod tests {
             "folder/folder2/file2".to_string(),
             Node {
                 path: "folder/folder2/file2".to_string(),
-                content: Some("".to_string()),
+                content: Some(b"".to_vec()),
+                kind: NodeKind::Blob,
                 git_url: "".to_string(),
                 sha: "x132".to_string(),
             },
@@ -176,7 +411,8 @@ mod tests {
             "folder/file3".to_string(),
             Node {
                 path: "folder/file3".to_string(),
-                content: Some("".to_string()),
+                content: Some(b"".to_vec()),
+                kind: NodeKind::Blob,
                 git_url: "".to_string(),
                 sha: "x132".to_string(),
             },
@@ -191,7 +427,8 @@ mod tests {
             "folder/folder2/file2".to_string(),
             Node {
                 path: "folder/folder2/file2".to_string(),
-                content: Some("".to_string()),
+                content: Some(b"".to_vec()),
+                kind: NodeKind::Blob,
                 git_url: "".to_string(),
                 sha: "x132".to_string(),
             },
@@ -199,4 +436,506 @@ mod tests {
 
         assert_eq!(new_tree, expected_tree);
     }
+
+    #[test]
+    fn test_get_blob_sha() {
+        assert_eq!(
+            get_blob_sha(&None),
+            "e69de29bb2d1d6434b8b29ae775ad8c2e48c5391"
+        );
+        assert_eq!(
+            get_blob_sha(&Some(b"hello\n".to_vec())),
+            "ce013625030ba8dba906f756967f9e9ca394464a"
+        );
+    }
+
+    #[test]
+    fn test_is_binary() {
+        assert!(!is_binary(b""));
+        assert!(!is_binary("plain text, ünïcödé\n".as_bytes()));
+        assert!(is_binary(b"\x89PNG\r\n\x1a\n\x00\x00"));
+        assert!(is_binary(b"latin-1 \xe9t\xe9"));
+    }
+
+    #[test]
+    fn test_generate_events_skips_unchanged_files() {
+        let source_tree = Tree::from([
+            (
+                "unchanged".to_string(),
+                Node {
+                    path: "unchanged".to_string(),
+                    content: Some(b"hello\n".to_vec()),
+                    kind: NodeKind::Blob,
+                    git_url: "".to_string(),
+                    sha: "".to_string(),
+                },
+            ),
+            (
+                "changed".to_string(),
+                Node {
+                    path: "changed".to_string(),
+                    content: Some(b"new content".to_vec()),
+                    kind: NodeKind::Blob,
+                    git_url: "".to_string(),
+                    sha: "".to_string(),
+                },
+            ),
+        ]);
+        let destination_tree = Tree::from([
+            (
+                "unchanged".to_string(),
+                Node {
+                    path: "unchanged".to_string(),
+                    content: Some(b"hello\n".to_vec()),
+                    kind: NodeKind::Blob,
+                    git_url: "".to_string(),
+                    sha: "ce013625030ba8dba906f756967f9e9ca394464a".to_string(),
+                },
+            ),
+            (
+                "changed".to_string(),
+                Node {
+                    path: "changed".to_string(),
+                    content: Some(b"hello\n".to_vec()),
+                    kind: NodeKind::Blob,
+                    git_url: "".to_string(),
+                    sha: "ce013625030ba8dba906f756967f9e9ca394464a".to_string(),
+                },
+            ),
+        ]);
+
+        let events = source_tree.generate_events(&destination_tree);
+
+        assert_eq!(
+            events,
+            vec![Event::Update {
+                path: "changed".to_string(),
+                content: Some(b"new content".to_vec()),
+                kind: NodeKind::Blob,
+                sha: "ce013625030ba8dba906f756967f9e9ca394464a".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_update_by_pattern() {
+        let pattern = Regex::new(r"version = \S+").unwrap();
+
+        assert_eq!(
+            update_by_pattern(
+                &pattern,
+                "version = 2\nsource = true\n",
+                "name = a\nversion = 1\nlocal = true\n"
+            ),
+            "name = a\nversion = 2\nlocal = true\n"
+        );
+        assert_eq!(
+            update_by_pattern(&pattern, "version = 2\n", "version = 1\nversion = 1\n"),
+            "version = 2\nversion = 1\n"
+        );
+        assert_eq!(
+            update_by_pattern(&pattern, "no match\n", "version = 1\n"),
+            "version = 1\n"
+        );
+    }
+
+    #[test]
+    fn test_apply_update_fns() {
+        let source_tree = Tree::from([
+            (
+                "existing".to_string(),
+                Node {
+                    path: "existing".to_string(),
+                    content: Some(b"source header\nversion = 2\n".to_vec()),
+                    kind: NodeKind::Blob,
+                    git_url: "".to_string(),
+                    sha: "".to_string(),
+                },
+            ),
+            (
+                "new".to_string(),
+                Node {
+                    path: "new".to_string(),
+                    content: Some(b"version = 2\n".to_vec()),
+                    kind: NodeKind::Blob,
+                    git_url: "".to_string(),
+                    sha: "".to_string(),
+                },
+            ),
+        ]);
+        let destination_tree = Tree::from([(
+            "existing".to_string(),
+            Node {
+                path: "existing".to_string(),
+                content: Some(b"destination header\nversion = 1\n".to_vec()),
+                kind: NodeKind::Blob,
+                git_url: "".to_string(),
+                sha: "".to_string(),
+            },
+        )]);
+        let update_fns = Some(vec![UpdateFn::UpdateByPattern {
+            args: UpdateByPatternArgs {
+                pattern: CustomRegex(Regex::new(r"version = \S+").unwrap()),
+            },
+        }]);
+
+        let new_tree = source_tree.apply_update_fns(&destination_tree, &update_fns);
+
+        let expected_tree = Tree::from([
+            (
+                "existing".to_string(),
+                Node {
+                    path: "existing".to_string(),
+                    content: Some(b"destination header\nversion = 2\n".to_vec()),
+                    kind: NodeKind::Blob,
+                    git_url: "".to_string(),
+                    sha: "".to_string(),
+                },
+            ),
+            (
+                "new".to_string(),
+                Node {
+                    path: "new".to_string(),
+                    content: Some(b"version = 2\n".to_vec()),
+                    kind: NodeKind::Blob,
+                    git_url: "".to_string(),
+                    sha: "".to_string(),
+                },
+            ),
+        ]);
+        assert_eq!(new_tree, expected_tree);
+    }
+
+    #[test]
+    fn test_generate_events_detects_mode_changes() {
+        let source_tree = Tree::from([(
+            "script.sh".to_string(),
+            Node {
+                path: "script.sh".to_string(),
+                content: Some(b"hello\n".to_vec()),
+                kind: NodeKind::Executable,
+                git_url: "".to_string(),
+                sha: "".to_string(),
+            },
+        )]);
+        let destination_tree = Tree::from([(
+            "script.sh".to_string(),
+            Node {
+                path: "script.sh".to_string(),
+                content: Some(b"hello\n".to_vec()),
+                kind: NodeKind::Blob,
+                git_url: "".to_string(),
+                sha: "ce013625030ba8dba906f756967f9e9ca394464a".to_string(),
+            },
+        )]);
+
+        let events = source_tree.generate_events(&destination_tree);
+
+        assert_eq!(
+            events,
+            vec![Event::Update {
+                path: "script.sh".to_string(),
+                content: Some(b"hello\n".to_vec()),
+                kind: NodeKind::Executable,
+                sha: "ce013625030ba8dba906f756967f9e9ca394464a".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_node_kind_from_mode() {
+        assert_eq!(NodeKind::from_mode("100644"), Some(NodeKind::Blob));
+        assert_eq!(NodeKind::from_mode("100755"), Some(NodeKind::Executable));
+        assert_eq!(NodeKind::from_mode("120000"), Some(NodeKind::Symlink));
+        assert_eq!(NodeKind::from_mode("160000"), Some(NodeKind::Gitlink));
+        assert_eq!(NodeKind::from_mode("040000"), None);
+    }
+
+    fn create_node(path: &str, content: &str) -> Node {
+        Node {
+            path: path.to_string(),
+            content: Some(content.as_bytes().to_vec()),
+            kind: NodeKind::Blob,
+            git_url: "".to_string(),
+            sha: "".to_string(),
+        }
+    }
+
+    fn create_tree(nodes: Vec<Node>) -> Tree {
+        nodes
+            .into_iter()
+            .map(|node| (node.path.clone(), node))
+            .collect()
+    }
+
+    #[test]
+    fn test_apply_rename_and_delete() {
+        let tree = create_tree(vec![
+            create_node("docs/intro.md", "intro"),
+            create_node("docs/cache.tmp", "cache"),
+        ]);
+        let transformations = Some(vec![
+            Transformation::Rename {
+                args: RenameArgs {
+                    before: CustomRegex(Regex::new(r"^docs/(?P<name>.*)\.md$").unwrap()),
+                    after: "guide/${name}.markdown".to_string(),
+                },
+                paths: None,
+            },
+            Transformation::Delete {
+                args: DeleteArgs {
+                    pattern: glob::Pattern::new("**/*.tmp").unwrap(),
+                },
+                paths: None,
+            },
+        ]);
+
+        let new_tree = tree.apply_transformations(&transformations, &HashMap::new());
+
+        assert_eq!(
+            new_tree,
+            create_tree(vec![create_node("guide/intro.markdown", "intro")])
+        );
+    }
+
+    #[test]
+    fn test_apply_prepend_and_append() {
+        let tree = create_tree(vec![create_node("lib.rs", "fn main() {}")]);
+
+        let prepend = Some(vec![Transformation::Prepend {
+            args: PrependArgs {
+                text: "// generated by syncy\n".to_string(),
+            },
+            paths: None,
+        }]);
+        assert_eq!(
+            tree.clone()
+                .apply_transformations(&prepend, &HashMap::new()),
+            create_tree(vec![create_node(
+                "lib.rs",
+                "// generated by syncy\nfn main() {}"
+            )])
+        );
+
+        let append = Some(vec![Transformation::Append {
+            args: AppendArgs {
+                text: "\n".to_string(),
+            },
+            paths: None,
+        }]);
+        assert_eq!(
+            tree.apply_transformations(&append, &HashMap::new()),
+            create_tree(vec![create_node("lib.rs", "fn main() {}\n")])
+        );
+    }
+
+    #[test]
+    fn test_apply_template() {
+        let tree = create_tree(vec![create_node(
+            "readme",
+            "${path} of ${source.name} at ${ source.sha } by ${team}, ${HOME} is kept",
+        )]);
+        let transformations = Some(vec![Transformation::Template {
+            args: TemplateArgs {
+                variables: HashMap::from([("team".to_string(), "platform".to_string())]),
+            },
+            paths: None,
+        }]);
+        let variables = HashMap::from([
+            ("source.name".to_string(), "repo1".to_string()),
+            ("source.sha".to_string(), "abc123".to_string()),
+        ]);
+
+        let new_tree = tree.apply_transformations(&transformations, &variables);
+
+        assert_eq!(
+            new_tree,
+            create_tree(vec![create_node(
+                "readme",
+                "readme of repo1 at abc123 by platform, ${HOME} is kept"
+            )])
+        );
+    }
+
+    #[test]
+    fn test_apply_scoped_transformations() {
+        let tree = create_tree(vec![
+            create_node("docs/readme.md", "syncy"),
+            create_node("docs/drafts/notes.md", "syncy"),
+            create_node("src/main.rs", "syncy"),
+        ]);
+        let transformations = Some(vec![
+            Transformation::Rename {
+                args: RenameArgs {
+                    before: CustomRegex(Regex::new("^(.*)$").unwrap()),
+                    after: "vendor/$1".to_string(),
+                },
+                paths: None,
+            },
+            Transformation::Replace {
+                args: ReplaceArgs {
+                    before: CustomRegex(Regex::new("syncy").unwrap()),
+                    after: "[syncy](https://github.com/frolovdev/syncy)".to_string(),
+                },
+                paths: Some(GlobExpression {
+                    include: vec![glob::Pattern::new("**/*.md").unwrap()],
+                    exclude: vec![glob::Pattern::new("vendor/docs/drafts/**").unwrap()],
+                }),
+            },
+        ]);
+
+        let new_tree = tree.apply_transformations(&transformations, &HashMap::new());
+
+        assert_eq!(
+            new_tree,
+            create_tree(vec![
+                create_node(
+                    "vendor/docs/readme.md",
+                    "[syncy](https://github.com/frolovdev/syncy)"
+                ),
+                create_node("vendor/docs/drafts/notes.md", "syncy"),
+                create_node("vendor/src/main.rs", "syncy"),
+            ])
+        );
+    }
+
+    fn create_move(before: &str, after: &str) -> Transformation {
+        Transformation::Move {
+            args: MoveArgs {
+                before: before.to_string(),
+                after: after.to_string(),
+            },
+            paths: None,
+        }
+    }
+
+    fn create_replace(before: &str, after: &str) -> Transformation {
+        Transformation::Replace {
+            args: ReplaceArgs {
+                before: CustomRegex(Regex::new(before).unwrap()),
+                after: after.to_string(),
+            },
+            paths: None,
+        }
+    }
+
+    #[test]
+    fn test_move_path() {
+        assert_eq!(move_path("a/b", "", "c"), Some("c/a/b".to_string()));
+        assert_eq!(move_path("a/b", "a", "c"), Some("c/b".to_string()));
+        assert_eq!(move_path("a/b", "a", ""), Some("b".to_string()));
+        assert_eq!(move_path("a/b", "a/b", "c/d"), Some("c/d".to_string()));
+        assert_eq!(move_path("ab/c", "a", "c"), None);
+        assert_eq!(move_path("b/a", "a", "c"), None);
+    }
+
+    #[test]
+    fn test_apply_chained_moves() {
+        let tree = create_tree(vec![
+            create_node("src/lib.rs", "lib"),
+            create_node("src2/lib.rs", "lib"),
+            create_node("readme", "readme"),
+        ]);
+        let transformations = Some(vec![
+            create_move("src", "lib"),
+            create_move("lib", "vendor/lib"),
+            create_move("vendor", "third_party/vendor"),
+        ]);
+
+        let new_tree = tree.apply_transformations(&transformations, &HashMap::new());
+
+        assert_eq!(
+            new_tree,
+            create_tree(vec![
+                create_node("third_party/vendor/lib/lib.rs", "lib"),
+                create_node("src2/lib.rs", "lib"),
+                create_node("readme", "readme"),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_apply_chained_replaces() {
+        let tree = create_tree(vec![create_node("a", "one two"), create_node("b", "three")]);
+        let transformations = Some(vec![
+            create_replace("one", "two"),
+            create_replace("two", "three"),
+            create_replace("three", "four"),
+        ]);
+
+        let new_tree = tree.apply_transformations(&transformations, &HashMap::new());
+
+        assert_eq!(
+            new_tree,
+            create_tree(vec![
+                create_node("a", "four four"),
+                create_node("b", "four")
+            ])
+        );
+    }
+
+    #[test]
+    fn test_apply_mixed_pipeline() {
+        let tree = create_tree(vec![create_node("docs/readme.md", "Hello")]);
+        let transformations = Some(vec![
+            create_replace("Hello", "Hi"),
+            create_move("docs", "guide"),
+            Transformation::Prepend {
+                args: PrependArgs {
+                    text: "<!-- ${path} -->\n".to_string(),
+                },
+                paths: None,
+            },
+            Transformation::Template {
+                args: TemplateArgs {
+                    variables: HashMap::new(),
+                },
+                paths: None,
+            },
+            create_replace("Hi", "Hey"),
+        ]);
+
+        let new_tree = tree.apply_transformations(&transformations, &HashMap::new());
+
+        assert_eq!(
+            new_tree,
+            create_tree(vec![create_node(
+                "guide/readme.md",
+                "<!-- guide/readme.md -->\nHey"
+            )])
+        );
+    }
+
+    #[test]
+    fn test_apply_reversed_transformations() {
+        let tree = create_tree(vec![
+            create_node("shared/config.yml", "url: https://example.com/v1.0"),
+            create_node("shared/nested/a", "v1.0 and $HOME"),
+        ]);
+        let transformations = Some(vec![
+            create_move("shared", "vendor/shared"),
+            create_replace(r"v1\.0", "v2.0 ($$)"),
+            create_move("vendor", "third_party"),
+        ]);
+
+        let transformed_tree = tree
+            .clone()
+            .apply_transformations(&transformations, &HashMap::new());
+        assert_eq!(
+            transformed_tree,
+            create_tree(vec![
+                create_node(
+                    "third_party/shared/config.yml",
+                    "url: https://example.com/v2.0 ($)"
+                ),
+                create_node("third_party/shared/nested/a", "v2.0 ($) and $HOME"),
+            ])
+        );
+
+        let reversed_transformations = reverse_transformations(&transformations).unwrap();
+        let reversed_tree =
+            transformed_tree.apply_transformations(&reversed_transformations, &HashMap::new());
+
+        assert_eq!(reversed_tree, tree);
+    }
 }
@@ -0,0 +1,166 @@
+use chrono::{DateTime, Duration, Utc};
+use octocrab::Octocrab;
+use serde::Deserialize;
+use std::{collections::HashMap, sync::Arc};
+use tokio::sync::Mutex;
+
+use crate::cli::{Connection, Credentials};
+use crate::error::{Error, Result};
+use crate::github_provider::{configure_instance, get_api_root};
+
+/// Installation tokens are replaced this long before they expire, so a token
+/// doesn't run out in the middle of syncing a repository.
+const TOKEN_EXPIRY_MARGIN_MINUTES: i64 = 10;
+
+/// Hands out the octocrab instance to reach a repository with. Every
+/// connection, i.e. API root and credentials, gets its own client, shared by
+/// the repositories reached through it.
+#[derive(Default)]
+pub struct GithubClients {
+    clients: std::sync::Mutex<Vec<(Connection, Arc<ConnectionClient>)>>,
+}
+
+/// A token works for every repository, while a GitHub App needs an
+/// installation token per repository owner, since every owner installs the
+/// app separately.
+enum ConnectionClient {
+    Token(Arc<Octocrab>),
+    App(AppInstallations),
+}
+
+impl GithubClients {
+    pub async fn get(
+        &self,
+        connection: &Connection,
+        owner: &str,
+        repo: &str,
+    ) -> Result<Arc<Octocrab>> {
+        match self.get_client(connection)?.as_ref() {
+            ConnectionClient::Token(instance) => Ok(instance.clone()),
+            ConnectionClient::App(installations) => installations.get(owner, repo).await,
+        }
+    }
+
+    fn get_client(&self, connection: &Connection) -> Result<Arc<ConnectionClient>> {
+        let mut clients = self.clients.lock().expect("clients lock is not poisoned");
+        if let Some((_, client)) = clients.iter().find(|(known, _)| known == connection) {
+            return Ok(client.clone());
+        }
+
+        let instance = configure_instance(&connection.credentials, connection.api_url.clone())?;
+        let client = Arc::new(match connection.credentials {
+            Credentials::Token(_) => ConnectionClient::Token(instance),
+            Credentials::App(_) => ConnectionClient::App(AppInstallations {
+                app: instance,
+                base_url: connection.api_url.clone(),
+                installations: Mutex::new(HashMap::new()),
+            }),
+        });
+        clients.push((connection.clone(), client.clone()));
+
+        Ok(client)
+    }
+}
+
+struct AppInstallations {
+    app: Arc<Octocrab>,
+    base_url: Option<String>,
+    installations: Mutex<HashMap<String, Installation>>,
+}
+
+struct Installation {
+    instance: Arc<Octocrab>,
+    expires_at: DateTime<Utc>,
+}
+
+impl AppInstallations {
+    /// Returns the instance of the installation on `owner`, minting a new
+    /// installation token when there is none yet or it is about to expire.
+    async fn get(&self, owner: &str, repo: &str) -> Result<Arc<Octocrab>> {
+        // held while minting, so concurrent destinations of an owner share one token
+        let mut installations = self.installations.lock().await;
+
+        let key = owner.to_lowercase();
+        let refresh_at = Utc::now() + Duration::minutes(TOKEN_EXPIRY_MARGIN_MINUTES);
+        if let Some(installation) = installations.get(&key) {
+            if installation.expires_at > refresh_at {
+                return Ok(installation.instance.clone());
+            }
+        }
+
+        let installation = self.create_installation(owner, repo).await?;
+        let instance = installation.instance.clone();
+        installations.insert(key, installation);
+
+        Ok(instance)
+    }
+
+    async fn create_installation(&self, owner: &str, repo: &str) -> Result<Installation> {
+        let installation_id = get_repository_installation(&self.app, owner, repo)
+            .await
+            .map_err(|error| {
+                Error::Provider(format!(
+                    "can't find the app installation for {}/{}: {}",
+                    owner, repo, error
+                ))
+            })?
+            .id;
+        let access_token = create_installation_token(&self.app, installation_id).await?;
+
+        let expires_at = DateTime::parse_from_rfc3339(&access_token.expires_at)
+            .map_err(|error| {
+                Error::Provider(format!(
+                    "invalid expires_at `{}` of the installation token: {}",
+                    access_token.expires_at, error
+                ))
+            })?
+            .with_timezone(&Utc);
+
+        let mut octocrab_builder = Octocrab::builder().personal_token(access_token.token);
+        if let Some(base_url) = &self.base_url {
+            octocrab_builder = octocrab_builder.base_url(get_api_root(base_url))?;
+        }
+
+        Ok(Installation {
+            instance: Arc::new(octocrab_builder.build()?),
+            expires_at,
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct InstallationInfo {
+    id: u64,
+}
+
+async fn get_repository_installation(
+    octocrab: &Arc<Octocrab>,
+    owner: &str,
+    repo: &str,
+) -> octocrab::Result<InstallationInfo> {
+    let route = format!(
+        "repos/{owner}/{repo}/installation",
+        owner = owner,
+        repo = repo
+    );
+
+    octocrab.get(route, None::<&()>).await
+}
+
+#[derive(Deserialize)]
+struct InstallationToken {
+    token: String,
+    expires_at: String,
+}
+
+async fn create_installation_token(
+    octocrab: &Arc<Octocrab>,
+    installation_id: u64,
+) -> octocrab::Result<InstallationToken> {
+    let route = format!(
+        "app/installations/{installation_id}/access_tokens",
+        installation_id = installation_id
+    );
+
+    octocrab.post(route, None::<&()>).await
+}
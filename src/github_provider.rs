@@ -1,61 +1,77 @@
-use async_recursion::async_recursion;
 use async_trait::async_trait;
-use core::panic;
-use git_tree::GitTree;
-use octocrab::models::repos::{Commit, Content, ContentItems};
+use futures::{stream, StreamExt, TryStreamExt};
+use git_tree::{GitTree, NodeKind};
 use octocrab::{models, params::repos::Reference, Octocrab};
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::{collections::HashMap, future::Future, sync::Arc};
+use tokio::sync::Semaphore;
 
-use crate::cli::WorkDirExpression;
-use crate::cli::{DestinationRepository, ParsedConfig};
+use crate::cli::{Credentials, DestinationRepository, GithubApp, ParsedConfig, SourceRepository};
+use crate::cli::{Transformation, WorkDirExpression};
+use crate::error::{Error, Result};
+use crate::event::Event;
 use crate::git_tree;
 use crate::provider::Provider;
 
 pub struct GithubProvider {
     pub config: ParsedConfig,
+    pub requests: RequestLimit,
 }
 
-#[async_trait]
-impl Provider<Arc<octocrab::Octocrab>> for GithubProvider {
-    fn configure_provider(&self, base_url: Option<String>) -> Arc<octocrab::Octocrab> {
-        let octacrab_builder =
-            octocrab::Octocrab::builder().personal_token(self.config.token.clone());
-
-        if let Some(unwraped_base_url) = base_url {
-            let octacrab_builder = octacrab_builder.base_url(unwraped_base_url).unwrap();
+/// Caps the requests in flight at `max_concurrency`, across every destination
+/// synced at the same time and the blobs each of them fetches or creates.
+#[derive(Clone)]
+pub struct RequestLimit {
+    permits: Arc<Semaphore>,
+    max_concurrency: usize,
+}
 
-            octocrab::initialise(octacrab_builder).unwrap();
+impl RequestLimit {
+    pub fn new(max_concurrency: usize) -> Self {
+        RequestLimit {
+            permits: Arc::new(Semaphore::new(max_concurrency)),
+            max_concurrency,
+        }
+    }
 
-            let instance: Arc<octocrab::Octocrab> = octocrab::instance();
+    /// Sends `request` once a permit is free. `request` should not run other
+    /// limited requests, which would wait for a permit while holding one.
+    pub async fn run<F: Future>(&self, request: F) -> F::Output {
+        let _permit = self
+            .permits
+            .acquire()
+            .await
+            .expect("the request permits are never closed");
 
-            return instance;
-        }
+        request.await
+    }
+}
 
-        octocrab::initialise(octacrab_builder).unwrap();
+impl GithubProvider {
+    pub fn new(config: ParsedConfig) -> Self {
+        let requests = RequestLimit::new(config.max_concurrency);
 
-        let instance: Arc<octocrab::Octocrab> = octocrab::instance();
+        GithubProvider { config, requests }
+    }
 
-        instance
+    /// Returns a provider for `config`, e.g. the one of a single destination,
+    /// sharing the request limit with this one.
+    pub fn with_config(&self, config: ParsedConfig) -> Self {
+        GithubProvider {
+            config,
+            requests: self.requests.clone(),
+        }
     }
 
-    async fn create_source_tree(&self, instance: Arc<octocrab::Octocrab>) -> git_tree::Tree {
+    /// Returns the origin files of the source, before any transformation. It
+    /// is fetched once and transformed for every destination, whose
+    /// transformations may differ.
+    pub async fn fetch_source_tree(&self, instance: Arc<Octocrab>) -> Result<git_tree::Tree> {
         let root_path = match &self.config.origin_files {
             WorkDirExpression::Glob(_) => "",
             WorkDirExpression::Path(val) => val,
         };
 
-        let source_repo_content = get_repo(
-            &instance,
-            &self.config.source.owner,
-            &self.config.source.name,
-            &self.config.source.git_ref,
-            &root_path,
-        )
-        .await
-        .unwrap();
-
         let mut tree = git_tree::Tree::new();
 
         fill_tree_with_nodes(
@@ -63,246 +79,726 @@ impl Provider<Arc<octocrab::Octocrab>> for GithubProvider {
             &self.config.source.owner,
             &self.config.source.name,
             &self.config.source.git_ref,
-            &source_repo_content,
+            &self.config.origin_files,
             &mut tree,
+            &self.requests,
         )
-        .await;
+        .await?;
 
-        let transformed_source_tree = tree.transform_tree(&self.config.origin_files, &root_path);
-        let transformed_source_tree_with_applied_transformations =
-            transformed_source_tree.apply_transformations(&self.config.transformations);
+        Ok(tree.transform_tree(&self.config.origin_files, root_path))
+    }
+
+    pub async fn transform_source_tree(
+        &self,
+        instance: Arc<Octocrab>,
+        tree: git_tree::Tree,
+    ) -> Result<git_tree::Tree> {
+        let variables = get_template_variables(
+            &instance,
+            &self.config.source,
+            &self.config.transformations,
+            &self.requests,
+        )
+        .await?;
 
-        transformed_source_tree_with_applied_transformations
+        Ok(tree.apply_transformations(&self.config.transformations, &variables))
+    }
+}
+
+#[async_trait]
+impl Provider<Arc<octocrab::Octocrab>> for GithubProvider {
+    /// Authenticates with the top-level credentials, `base_url` defaults to the
+    /// top-level `api_url`.
+    fn configure_provider(&self, base_url: Option<String>) -> Result<Arc<octocrab::Octocrab>> {
+        configure_instance(
+            &self.config.credentials,
+            base_url.or_else(|| self.config.api_url.clone()),
+        )
+    }
+
+    async fn create_source_tree(
+        &self,
+        instance: Arc<octocrab::Octocrab>,
+    ) -> Result<git_tree::Tree> {
+        let tree = self.fetch_source_tree(instance.clone()).await?;
+
+        self.transform_source_tree(instance, tree).await
+    }
+
+    async fn get_destination_base_branch(
+        &self,
+        instance: Arc<octocrab::Octocrab>,
+        destination: &DestinationRepository,
+    ) -> Result<String> {
+        if let Some(git_ref) = &destination.git_ref {
+            return Ok(git_ref.to_string());
+        }
+
+        let repository = self
+            .requests
+            .run(get_repository(
+                &instance,
+                &destination.owner,
+                &destination.name,
+            ))
+            .await?;
+
+        Ok(repository.default_branch)
     }
 
     async fn create_destination_branch(
         &self,
         instance: Arc<octocrab::Octocrab>,
         destination: &DestinationRepository,
+        base_branch: &str,
         destination_branch_name: &str,
-    ) {
-        let main_ref = "main";
-
-        let destination_main =
-            get_branch(&instance, &destination.owner, &destination.name, &main_ref)
-                .await
-                .unwrap();
+        tree_sha: &str,
+    ) -> Result<bool> {
+        let existing_branches = self
+            .requests
+            .run(get_matching_branches(
+                &instance,
+                &destination.owner,
+                &destination.name,
+                destination_branch_name,
+            ))
+            .await?;
+
+        let full_ref = format!("refs/heads/{}", destination_branch_name);
+        let exists = existing_branches.iter().any(|r| r.ref_field == full_ref);
+        if exists {
+            let head = self
+                .requests
+                .run(get_branch(
+                    &instance,
+                    &destination.owner,
+                    &destination.name,
+                    destination_branch_name,
+                ))
+                .await?;
+            let head_commit = self
+                .requests
+                .run(get_commit(
+                    &instance,
+                    &destination.owner,
+                    &destination.name,
+                    &get_sha(&head.object)?,
+                ))
+                .await?;
+
+            if head_commit.tree.sha == tree_sha {
+                return Ok(false);
+            }
+        }
 
-        let commit_ref = get_sha(&destination_main.object).unwrap();
+        let destination_base = self
+            .requests
+            .run(get_branch(
+                &instance,
+                &destination.owner,
+                &destination.name,
+                base_branch,
+            ))
+            .await?;
+
+        let commit_ref = get_sha(&destination_base.object)?;
+
+        if exists {
+            self.requests
+                .run(update_branch(
+                    &instance,
+                    &destination.owner,
+                    &destination.name,
+                    destination_branch_name,
+                    &commit_ref,
+                    true,
+                ))
+                .await?;
+        } else {
+            self.requests
+                .run(create_branch(
+                    &instance,
+                    &destination.owner,
+                    &destination.name,
+                    destination_branch_name,
+                    &commit_ref,
+                ))
+                .await?;
+        }
 
-        create_branch(
-            &instance,
-            &destination.owner,
-            &destination.name,
-            &destination_branch_name,
-            &commit_ref,
-        )
-        .await
-        .unwrap();
+        Ok(true)
     }
 
     async fn create_destination_tree(
         &self,
         instance: Arc<Octocrab>,
         destination: &DestinationRepository,
-    ) -> git_tree::Tree {
+        base_branch: &str,
+    ) -> Result<git_tree::Tree> {
         let root_path = match &self.config.destination_files {
             WorkDirExpression::Glob(_) => "",
             WorkDirExpression::Path(val) => val,
         };
 
-        let main_ref = "main";
-
-        let repo_content = get_repo(
-            &instance,
-            &destination.owner,
-            &destination.name,
-            &main_ref,
-            &root_path,
-        )
-        .await
-        .unwrap();
-
         let mut destination_tree = git_tree::Tree::new();
         fill_tree_with_nodes(
             &instance,
             &destination.owner,
             &destination.name,
-            &main_ref,
-            &repo_content,
+            base_branch,
+            &self.config.destination_files,
             &mut destination_tree,
+            &self.requests,
         )
-        .await;
+        .await?;
 
-        let transformed_destination_tree =
-            destination_tree.transform_tree(&self.config.destination_files, &root_path);
-
-        transformed_destination_tree
+        Ok(destination_tree.transform_tree(&self.config.destination_files, root_path))
     }
 
-    async fn create_file(
+    async fn create_commit_tree(
+        &self,
         instance: Arc<Octocrab>,
         destination: &DestinationRepository,
-        path: &str,
-        content: &Option<String>,
-        destination_branch_name: &str,
-    ) {
-        create_file(
-            &instance,
-            &destination.owner,
-            &destination.name,
-            &path,
-            content.as_ref(),
-            &destination_branch_name,
-        )
-        .await;
+        events: &[Event],
+        base_branch: &str,
+    ) -> Result<String> {
+        let destination_base = self
+            .requests
+            .run(get_branch(
+                &instance,
+                &destination.owner,
+                &destination.name,
+                base_branch,
+            ))
+            .await?;
+
+        let base_commit = self
+            .requests
+            .run(get_commit(
+                &instance,
+                &destination.owner,
+                &destination.name,
+                &get_sha(&destination_base.object)?,
+            ))
+            .await?;
+
+        let mut requests = Vec::new();
+        for event in events.iter() {
+            requests.push(
+                self.requests
+                    .run(create_tree_entry(&instance, destination, event)),
+            );
+        }
+
+        let entries: Vec<TreeEntry> = stream::iter(requests)
+            .buffered(self.config.max_concurrency)
+            .try_collect()
+            .await?;
+
+        let tree = self
+            .requests
+            .run(create_tree(
+                &instance,
+                &destination.owner,
+                &destination.name,
+                &base_commit.tree.sha,
+                entries,
+            ))
+            .await?;
+
+        Ok(tree.sha)
     }
 
-    async fn update_file(
+    async fn create_commit(
+        &self,
         instance: Arc<Octocrab>,
         destination: &DestinationRepository,
-        path: &str,
-        content: &Option<String>,
-        sha: &str,
+        tree_sha: &str,
         destination_branch_name: &str,
-    ) {
-        update_file(
-            &instance,
-            &destination.owner,
-            &destination.name,
-            path,
-            content.as_ref(),
-            sha,
-            &destination_branch_name,
-        )
-        .await
+    ) -> Result<()> {
+        let destination_branch = self
+            .requests
+            .run(get_branch(
+                &instance,
+                &destination.owner,
+                &destination.name,
+                destination_branch_name,
+            ))
+            .await?;
+
+        let parent_sha = get_sha(&destination_branch.object)?;
+
+        let message = get_pull_request_name(
+            &self.config.source.owner,
+            &self.config.source.name,
+            &self.config.source.git_ref,
+        );
+
+        let commit = self
+            .requests
+            .run(create_commit(
+                &instance,
+                &destination.owner,
+                &destination.name,
+                &message,
+                tree_sha,
+                &parent_sha,
+            ))
+            .await?;
+
+        self.requests
+            .run(update_branch(
+                &instance,
+                &destination.owner,
+                &destination.name,
+                destination_branch_name,
+                &commit.sha,
+                false,
+            ))
+            .await
+            .map_err(|error| match error {
+                octocrab::Error::GitHub { source, .. } => Error::Conflict(format!(
+                    "{}/{} branch {} was updated while syncing: {}",
+                    destination.owner, destination.name, destination_branch_name, source
+                )),
+                error => Error::Http(error),
+            })?;
+
+        Ok(())
     }
 
-    async fn delete_file(
-        instance: Arc<Octocrab>,
+    fn get_destination_branch(&self) -> String {
+        get_destination_branch_name(&self.config.source, self.config.job.as_deref())
+    }
+
+    async fn create_pull_request_destination(
+        &self,
+        instance: Arc<octocrab::Octocrab>,
         destination: &DestinationRepository,
-        path: &str,
-        sha: &str,
+        base_branch: &str,
         destination_branch_name: &str,
-    ) {
-        delete_file(
-            &instance,
-            &destination.owner,
-            &destination.name,
-            &path,
-            &sha,
-            &destination_branch_name,
-        )
-        .await;
-    }
+    ) -> Result<()> {
+        let web_url = self
+            .config
+            .get_connection(&self.config.source.connection)
+            .web_url;
+        let open_pull_requests = self
+            .requests
+            .run(get_open_pull_requests(
+                &instance,
+                &destination.owner,
+                &destination.name,
+                destination_branch_name,
+            ))
+            .await?;
+
+        let pull_request = match open_pull_requests.first() {
+            Some(pull_request) => {
+                self.requests
+                    .run(update_pull_request(
+                        &instance,
+                        &destination.owner,
+                        &destination.name,
+                        &self.config,
+                        &web_url,
+                        pull_request.number,
+                    ))
+                    .await?
+            }
+            None => {
+                self.requests
+                    .run(create_pull_request(
+                        &instance,
+                        &destination.owner,
+                        &destination.name,
+                        &self.config,
+                        &web_url,
+                        destination_branch_name,
+                        base_branch,
+                    ))
+                    .await?
+            }
+        };
 
-    fn get_destination_branch(&self) -> String {
-        let branch =
-            get_destination_branch_name(&self.config.source.owner, &self.config.source.name);
+        let labels = self
+            .config
+            .pull_request
+            .labels
+            .as_deref()
+            .unwrap_or_default();
+        if !labels.is_empty() {
+            self.requests
+                .run(add_labels(
+                    &instance,
+                    &destination.owner,
+                    &destination.name,
+                    pull_request.number,
+                    labels,
+                ))
+                .await?;
+        }
 
-        branch
+        Ok(())
     }
 
-    async fn create_pull_request_destination(
+    async fn close_pull_request_destination(
         &self,
         instance: Arc<octocrab::Octocrab>,
         destination: &DestinationRepository,
         destination_branch_name: &str,
-    ) {
-        let main_ref = "main";
-        create_pull_request(
-            &instance,
-            &destination.owner,
-            &destination.name,
-            &self.config.source.owner,
-            &self.config.source.name,
-            &self.config.source.git_ref,
-            &destination_branch_name,
-            &main_ref,
-        )
-        .await;
+    ) -> Result<Option<u64>> {
+        let open_pull_requests = self
+            .requests
+            .run(get_open_pull_requests(
+                &instance,
+                &destination.owner,
+                &destination.name,
+                destination_branch_name,
+            ))
+            .await?;
+
+        let pull_request = match open_pull_requests.first() {
+            Some(pull_request) => pull_request,
+            None => return Ok(None),
+        };
+
+        self.requests
+            .run(close_pull_request(
+                &instance,
+                &destination.owner,
+                &destination.name,
+                pull_request.number,
+            ))
+            .await?;
+
+        Ok(Some(pull_request.number))
     }
 }
 
-pub async fn fill_tree_with_nodes<'a>(
+/// Authenticates with the token or, for a GitHub App, as the app itself:
+/// `GithubClients` then mints the installation tokens.
+pub fn configure_instance(
+    credentials: &Credentials,
+    base_url: Option<String>,
+) -> Result<Arc<octocrab::Octocrab>> {
+    let mut octacrab_builder = match credentials {
+        Credentials::Token(token) => {
+            octocrab::Octocrab::builder().personal_token(token.as_str().to_string())
+        }
+        Credentials::App(app) => {
+            octocrab::Octocrab::builder().app(models::AppId(app.id), read_private_key(app)?)
+        }
+    };
+
+    if let Some(unwraped_base_url) = base_url {
+        octacrab_builder = octacrab_builder.base_url(get_api_root(&unwraped_base_url))?;
+    }
+
+    Ok(Arc::new(octacrab_builder.build()?))
+}
+
+/// Routes are joined onto the base url, which keeps a path such as the
+/// `/api/v3` of a GitHub Enterprise Server only when it ends with a `/`.
+pub fn get_api_root(base_url: &str) -> String {
+    format!("{}/", base_url.trim_end_matches('/'))
+}
+
+fn read_private_key(app: &GithubApp) -> Result<jsonwebtoken::EncodingKey> {
+    let path = app.private_key_path.display();
+    let key = std::fs::read(&app.private_key_path).map_err(|error| {
+        Error::Config(format!("could not read private key `{}`: {}", path, error))
+    })?;
+
+    jsonwebtoken::EncodingKey::from_rsa_pem(&key)
+        .map_err(|error| Error::Config(format!("invalid private key `{}`: {}", path, error)))
+}
+
+/// Lists the subtrees of `git_ref` the work dir can match in, each with a
+/// single request, and downloads the blobs matching `work_dir`, sending every
+/// request within `requests`.
+pub async fn fill_tree_with_nodes(
     instance: &Arc<Octocrab>,
     owner: &str,
     repo: &str,
     git_ref: &str,
-    content_items: &ContentItems,
+    work_dir: &WorkDirExpression,
     tree: &mut git_tree::Tree,
-) {
-    for x in content_items.items.iter() {
-        match x {
-            Content { r#type, path, .. } => {
-                let file_type = "file";
-                let folder_type = "dir";
-                if r#type == file_type {
-                    unwrap_file(&instance, &path, &owner, &repo, &git_ref, tree).await;
-                } else if r#type == folder_type {
-                    unwrap_folder(&instance, &owner, &repo, &git_ref, &x, tree).await;
-                } else {
-                    panic!("unexpected content type")
-                }
-            }
-        };
+    requests: &RequestLimit,
+) -> Result<()> {
+    let mut entries = Vec::new();
+    for root_path in work_dir.root_paths() {
+        entries
+            .extend(list_tree_entries(instance, owner, repo, git_ref, &root_path, requests).await?);
+    }
+
+    let mut blob_requests = Vec::new();
+    for entry in entries.iter() {
+        if entry.r#type == TREE_TYPE || !work_dir.matches(&entry.path) {
+            continue;
+        }
+
+        let kind = NodeKind::from_mode(&entry.mode).ok_or_else(|| {
+            Error::Provider(format!(
+                "unsupported mode {} of {} in {}/{}",
+                entry.mode, entry.path, owner, repo
+            ))
+        })?;
+        blob_requests.push(requests.run(unwrap_entry(instance, owner, repo, entry, kind)));
+    }
+
+    let nodes: Vec<git_tree::Node> = stream::iter(blob_requests)
+        .buffer_unordered(requests.max_concurrency)
+        .try_collect()
+        .await?;
+
+    for node in nodes {
+        tree.insert(node.path.clone(), node);
     }
+
+    Ok(())
 }
 
-async fn unwrap_file(
+/// Lists every entry under `root_path` with paths relative to the repository
+/// root. The subtree is resolved one level at a time, so only the listing of
+/// `root_path` itself is recursive, unless it is too large for a single
+/// response.
+async fn list_tree_entries(
     instance: &Arc<Octocrab>,
-    file_path: &str,
     owner: &str,
     repo: &str,
     git_ref: &str,
-    tree: &mut git_tree::Tree,
-) -> () {
-    let content_items = get_repo(&instance, &owner, &repo, &git_ref, &file_path.to_string())
-        .await
-        .unwrap();
+    root_path: &str,
+    requests: &RequestLimit,
+) -> Result<Vec<GitTreeEntry>> {
+    let mut tree_sha = git_ref.to_string();
+    if !root_path.is_empty() {
+        let components: Vec<&str> = root_path.split('/').collect();
+        for (index, component) in components.iter().enumerate() {
+            let level = requests
+                .run(get_git_tree(instance, owner, repo, &tree_sha, false))
+                .await?;
+
+            let entry = match level
+                .tree
+                .into_iter()
+                .find(|entry| entry.path == *component)
+            {
+                Some(entry) => entry,
+                None => return Ok(Vec::new()),
+            };
+
+            // the root path points to a file rather than a folder
+            if entry.r#type != TREE_TYPE {
+                return Ok(vec![GitTreeEntry {
+                    path: components[..=index].join("/"),
+                    ..entry
+                }]);
+            }
+
+            tree_sha = entry.sha;
+        }
+    }
 
-    let content = content_items.items.first().unwrap();
-    let decoded_content = content.decoded_content();
+    let git_tree = requests
+        .run(get_git_tree(instance, owner, repo, &tree_sha, true))
+        .await?;
 
-    let created_node = git_tree::Node {
-        path: file_path.to_string(),
-        content: decoded_content,
-        git_url: content.git_url.clone(),
-        sha: content.sha.clone(),
+    let entries = if git_tree.truncated {
+        list_tree_levels(instance, owner, repo, &git_tree.sha, requests).await?
+    } else {
+        git_tree.tree
     };
-    tree.insert(file_path.to_string(), created_node);
+
+    let entries = entries
+        .into_iter()
+        .map(|entry| match root_path {
+            "" => entry,
+            _ => GitTreeEntry {
+                path: format!("{}/{}", root_path, entry.path),
+                ..entry
+            },
+        })
+        .collect();
+
+    Ok(entries)
 }
 
-#[async_recursion()]
-async fn unwrap_folder(
+/// Lists a tree with a non-recursive request per subtree, for a tree whose
+/// recursive listing got truncated. The paths are relative to the tree.
+async fn list_tree_levels(
     instance: &Arc<Octocrab>,
     owner: &str,
     repo: &str,
-    git_ref: &str,
-    content: &Content,
-    tree: &mut git_tree::Tree,
-) {
-    let content_items = get_repo(&instance, &owner, &repo, &git_ref, &content.path)
-        .await
-        .unwrap();
+    tree_sha: &str,
+    requests: &RequestLimit,
+) -> Result<Vec<GitTreeEntry>> {
+    let mut entries = Vec::new();
+    let mut level = vec![(String::new(), tree_sha.to_string())];
+    while !level.is_empty() {
+        let listings: Vec<(String, GitTreeResponse)> = stream::iter(level)
+            .map(|(prefix, sha)| async move {
+                let listing = requests
+                    .run(get_git_tree(instance, owner, repo, &sha, false))
+                    .await?;
+
+                Ok::<_, Error>((prefix, listing))
+            })
+            .buffer_unordered(requests.max_concurrency)
+            .try_collect()
+            .await?;
+
+        level = Vec::new();
+        for (prefix, listing) in listings {
+            for entry in listing.tree {
+                let entry = match prefix.as_str() {
+                    "" => entry,
+                    _ => GitTreeEntry {
+                        path: format!("{}/{}", prefix, entry.path),
+                        ..entry
+                    },
+                };
+
+                if entry.r#type == TREE_TYPE {
+                    level.push((entry.path.clone(), entry.sha.clone()));
+                }
+                entries.push(entry);
+            }
+        }
+    }
+
+    Ok(entries)
+}
+
+async fn unwrap_entry(
+    instance: &Arc<Octocrab>,
+    owner: &str,
+    repo: &str,
+    entry: &GitTreeEntry,
+    kind: NodeKind,
+) -> Result<git_tree::Node> {
+    // a submodule has no blob, the entry points to a commit in another repository
+    if kind == NodeKind::Gitlink {
+        return Ok(git_tree::Node {
+            path: entry.path.to_string(),
+            content: Some(entry.sha.clone().into_bytes()),
+            kind,
+            git_url: entry.url.clone().unwrap_or_default(),
+            sha: entry.sha.clone(),
+        });
+    }
+
+    let blob = get_blob(instance, owner, repo, &entry.sha).await?;
+
+    let content = decode_blob(&blob).map_err(|reason| {
+        Error::Provider(format!(
+            "can't read {} from {}/{}: {}",
+            entry.path, owner, repo, reason
+        ))
+    })?;
+
+    Ok(git_tree::Node {
+        path: entry.path.to_string(),
+        content: Some(content),
+        kind,
+        git_url: entry.url.clone().unwrap_or_default(),
+        sha: entry.sha.clone(),
+    })
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct GitTreeEntry {
+    path: String,
+    mode: String,
+    r#type: String,
+    sha: String,
+    url: Option<String>,
+}
 
-    fill_tree_with_nodes(instance, &owner, &repo, &git_ref, &content_items, tree).await;
+#[derive(Debug, Deserialize, PartialEq)]
+struct GitTreeResponse {
+    sha: String,
+    tree: Vec<GitTreeEntry>,
+    truncated: bool,
 }
 
-async fn get_repo(
+async fn get_git_tree(
     octocrab: &Arc<Octocrab>,
     owner: &str,
     repo: &str,
-    git_ref: &str,
-    path: &str,
-) -> Result<octocrab::models::repos::ContentItems, octocrab::Error> {
-    octocrab
-        .repos(owner, repo)
-        .get_content()
-        .path(path)
-        .r#ref(git_ref)
-        .send()
-        .await
+    tree_sha: &str,
+    recursive: bool,
+) -> octocrab::Result<GitTreeResponse> {
+    let route = format!(
+        "repos/{owner}/{repo}/git/trees/{tree_sha}",
+        owner = owner,
+        repo = repo,
+        tree_sha = tree_sha
+    );
+
+    if recursive {
+        octocrab.get(route, Some(&[("recursive", "1")])).await
+    } else {
+        octocrab.get(route, None::<&()>).await
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct GitBlob {
+    content: String,
+    encoding: String,
+    size: usize,
+}
+
+/// Decodes the blob content and makes sure nothing got lost on the way, so a
+/// file is never silently synced as empty or cut off.
+fn decode_blob(blob: &GitBlob) -> std::result::Result<Vec<u8>, String> {
+    let content = match blob.encoding.as_str() {
+        "base64" => base64::decode(blob.content.replace('\n', ""))
+            .map_err(|err| format!("invalid base64 content: {}", err))?,
+        "utf-8" => blob.content.clone().into_bytes(),
+        encoding => return Err(format!("unsupported encoding `{}`", encoding)),
+    };
+
+    if content.len() != blob.size {
+        return Err(format!(
+            "expected {} bytes, got {}",
+            blob.size,
+            content.len()
+        ));
+    }
+
+    Ok(content)
+}
+
+async fn get_blob(
+    octocrab: &Arc<Octocrab>,
+    owner: &str,
+    repo: &str,
+    sha: &str,
+) -> octocrab::Result<GitBlob> {
+    let route = format!(
+        "repos/{owner}/{repo}/git/blobs/{sha}",
+        owner = owner,
+        repo = repo,
+        sha = sha
+    );
+
+    octocrab.get(route, None::<&()>).await
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct RepositoryInfo {
+    default_branch: String,
+}
+
+async fn get_repository(
+    octocrab: &Arc<Octocrab>,
+    owner: &str,
+    repo: &str,
+) -> octocrab::Result<RepositoryInfo> {
+    let route = format!("repos/{owner}/{repo}", owner = owner, repo = repo);
+
+    octocrab.get(route, None::<&()>).await
 }
 
 async fn get_branch(
@@ -310,7 +806,7 @@ async fn get_branch(
     owner: &str,
     repo: &str,
     git_ref: &str,
-) -> Result<octocrab::models::repos::Ref, octocrab::Error> {
+) -> octocrab::Result<octocrab::models::repos::Ref> {
     octocrab
         .repos(owner, repo)
         .get_ref(&Reference::Branch(git_ref.to_string()))
@@ -323,7 +819,7 @@ async fn create_branch(
     destination_repo: &str,
     destination_branch_name: &str,
     source_commit_ref: &str,
-) -> Result<octocrab::models::repos::Ref, octocrab::Error> {
+) -> octocrab::Result<octocrab::models::repos::Ref> {
     octocrab
         .repos(destination_owner, destination_repo)
         .create_ref(
@@ -333,163 +829,294 @@ async fn create_branch(
         .await
 }
 
-fn get_destination_branch_name(owner: &str, repo: &str) -> String {
-    let start = SystemTime::now();
-    let since_the_epoch = start
-        .duration_since(UNIX_EPOCH)
-        .expect("Time went backwards");
+async fn get_matching_branches(
+    octocrab: &Arc<Octocrab>,
+    owner: &str,
+    repo: &str,
+    branch: &str,
+) -> octocrab::Result<Vec<octocrab::models::repos::Ref>> {
+    let route = format!(
+        "repos/{owner}/{repo}/git/matching-refs/heads/{branch}",
+        owner = owner,
+        repo = repo,
+        branch = branch
+    );
+
+    octocrab.get(route, None::<&()>).await
+}
 
-    let in_ms = since_the_epoch.as_millis();
+/// The branch name is stable for a source so every run reuses the branch
+/// (and the pull request) left open by the previous one.
+/// Jobs syncing the same source into one repository each get their own
+/// branch, so they don't reset each other's commits.
+fn get_destination_branch_name(source: &SourceRepository, job: Option<&str>) -> String {
+    let prefix = match job {
+        Some(job) => format!("syncy/{}", job),
+        None => "syncy".to_string(),
+    };
 
     format!(
-        "syncy/{owner}/{repo}/{timestamp}",
-        owner = owner,
-        repo = repo,
-        timestamp = in_ms
+        "{prefix}/{owner}/{repo}/{branch}",
+        prefix = prefix,
+        owner = source.owner,
+        repo = source.name,
+        branch = source.git_ref
     )
 }
 
-fn get_sha(object: &models::repos::Object) -> Option<String> {
+/// Variables available to `builtin.template`. Resolving `source.sha` costs a
+/// request, so it only happens when a template is configured.
+async fn get_template_variables(
+    instance: &Arc<Octocrab>,
+    source: &SourceRepository,
+    transformations: &Option<Vec<Transformation>>,
+    requests: &RequestLimit,
+) -> Result<HashMap<String, String>> {
+    let mut variables = HashMap::from([
+        ("source.owner".to_string(), source.owner.to_string()),
+        ("source.name".to_string(), source.name.to_string()),
+        ("source.ref".to_string(), source.git_ref.to_string()),
+    ]);
+
+    let has_template = transformations
+        .iter()
+        .flatten()
+        .any(|transformation| matches!(transformation, Transformation::Template { .. }));
+    if has_template {
+        let commit = requests
+            .run(get_repository_commit(
+                instance,
+                &source.owner,
+                &source.name,
+                &source.git_ref,
+            ))
+            .await?;
+        variables.insert("source.sha".to_string(), commit.sha);
+    }
+
+    Ok(variables)
+}
+
+fn get_sha(object: &models::repos::Object) -> Result<String> {
     match object {
-        models::repos::Object::Commit { sha, .. } => Some(sha.to_string()),
-        _ => None,
+        models::repos::Object::Commit { sha, .. } => Ok(sha.to_string()),
+        _ => Err(Error::Provider(
+            "expected the branch to point to a commit".to_string(),
+        )),
     }
 }
 
-#[derive(Debug, Serialize)]
-struct CreateFileBody {
-    message: String,
-    content: String,
-    branch: String,
+const BLOB_MODE: &str = "100644";
+const BLOB_TYPE: &str = "blob";
+const TREE_TYPE: &str = "tree";
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct GitObject {
+    sha: String,
 }
 
 #[derive(Debug, Deserialize, PartialEq)]
-struct CreateFileResponse {
-    content: octocrab::models::repos::Content,
+struct GitCommit {
+    sha: String,
+    tree: GitObject,
 }
 
-async fn create_file(
+/// Resolves a branch, tag or sha to the sha of the commit it points to.
+async fn get_repository_commit(
     octocrab: &Arc<Octocrab>,
     owner: &str,
     repo: &str,
-    path: &str,
-    content: Option<&String>,
-    branch: &str,
-) -> CreateFileResponse {
-    let mapped_content = match content {
-        Some(value) => value,
-        None => "",
-    };
-
-    let encoded_content = base64::encode(mapped_content);
-
-    let body = CreateFileBody {
-        message: path.to_string(),
-        content: encoded_content,
-        branch: branch.to_string(),
-    };
-
+    git_ref: &str,
+) -> octocrab::Result<GitObject> {
     let route = format!(
-        "/repos/{owner}/{repo}/contents/{path}",
+        "repos/{owner}/{repo}/commits/{git_ref}",
         owner = owner,
         repo = repo,
-        path = path
+        git_ref = git_ref
     );
 
-    octocrab
-        .put::<CreateFileResponse, _, _>(route, Some(&body))
-        .await
-        .unwrap()
-}
-
-#[derive(Debug, Serialize)]
-struct DeleteFileBody {
-    message: String,
-    sha: String,
-    branch: String,
-}
-
-#[derive(Debug, Deserialize, PartialEq)]
-struct DeleteFileResponse {
-    content: Option<String>,
-    commit: Commit,
+    octocrab.get(route, None::<&()>).await
 }
 
-async fn delete_file(
+async fn get_commit(
     octocrab: &Arc<Octocrab>,
     owner: &str,
     repo: &str,
-    path: &str,
     sha: &str,
-    branch: &str,
-) -> DeleteFileResponse {
+) -> octocrab::Result<GitCommit> {
     let route = format!(
-        "/repos/{owner}/{repo}/contents/{path}",
+        "repos/{owner}/{repo}/git/commits/{sha}",
         owner = owner,
         repo = repo,
-        path = path
+        sha = sha
     );
 
-    let body = DeleteFileBody {
-        sha: sha.to_string(),
-        message: path.to_string(),
-        branch: branch.to_string(),
+    octocrab.get(route, None::<&()>).await
+}
+
+#[derive(Debug, Serialize)]
+struct CreateBlobBody {
+    content: String,
+    encoding: String,
+}
+
+async fn create_blob(
+    octocrab: &Arc<Octocrab>,
+    owner: &str,
+    repo: &str,
+    content: Option<&[u8]>,
+) -> octocrab::Result<GitObject> {
+    let body = CreateBlobBody {
+        content: base64::encode(content.unwrap_or_default()),
+        encoding: "base64".to_string(),
     };
 
-    octocrab
-        .delete::<DeleteFileResponse, _, _>(route, Some(&body))
-        .await
-        .unwrap()
+    let route = format!("repos/{owner}/{repo}/git/blobs", owner = owner, repo = repo);
+
+    octocrab.post(route, Some(&body)).await
 }
 
+async fn create_tree_entry(
+    octocrab: &Arc<Octocrab>,
+    destination: &DestinationRepository,
+    event: &Event,
+) -> Result<TreeEntry> {
+    let entry = match event {
+        Event::Create {
+            path,
+            content,
+            kind,
+        }
+        | Event::Update {
+            path,
+            content,
+            kind,
+            ..
+        } => {
+            let sha = match kind {
+                NodeKind::Gitlink => {
+                    String::from_utf8_lossy(content.as_deref().unwrap_or_default()).to_string()
+                }
+                _ => {
+                    create_blob(
+                        octocrab,
+                        &destination.owner,
+                        &destination.name,
+                        content.as_deref(),
+                    )
+                    .await?
+                    .sha
+                }
+            };
+
+            TreeEntry {
+                path: path.to_string(),
+                mode: kind.mode().to_string(),
+                r#type: kind.object_type().to_string(),
+                sha: Some(sha),
+            }
+        }
+        Event::Delete { path, .. } => TreeEntry {
+            path: path.to_string(),
+            mode: BLOB_MODE.to_string(),
+            r#type: BLOB_TYPE.to_string(),
+            sha: None,
+        },
+    };
+
+    Ok(entry)
+}
+
+/// An entry of the tree that is created on top of the destination branch.
+/// `sha: None` is serialized as `null` which removes the path from the tree.
 #[derive(Debug, Serialize)]
-struct UpdateFileBody {
-    message: String,
-    content: String,
-    branch: String,
-    sha: String,
+struct TreeEntry {
+    path: String,
+    mode: String,
+    r#type: String,
+    sha: Option<String>,
 }
 
-#[derive(Debug, Deserialize, PartialEq)]
-struct UpdateFileResponse {
-    content: octocrab::models::repos::Content,
-    commit: Commit,
+#[derive(Debug, Serialize)]
+struct CreateTreeBody {
+    base_tree: String,
+    tree: Vec<TreeEntry>,
 }
 
-async fn update_file(
+async fn create_tree(
     octocrab: &Arc<Octocrab>,
     owner: &str,
     repo: &str,
-    path: &str,
-    content: Option<&String>,
-    sha: &str,
-    branch: &str,
-) {
-    let mapped_content = match content {
-        Some(value) => value,
-        None => "",
+    base_tree: &str,
+    entries: Vec<TreeEntry>,
+) -> octocrab::Result<GitObject> {
+    let body = CreateTreeBody {
+        base_tree: base_tree.to_string(),
+        tree: entries,
     };
 
-    let encoded_content = base64::encode(mapped_content);
+    let route = format!("repos/{owner}/{repo}/git/trees", owner = owner, repo = repo);
 
-    let body = UpdateFileBody {
-        message: path.to_string(),
-        content: encoded_content,
-        branch: branch.to_string(),
+    octocrab.post(route, Some(&body)).await
+}
+
+#[derive(Debug, Serialize)]
+struct CreateCommitBody {
+    message: String,
+    tree: String,
+    parents: Vec<String>,
+}
+
+async fn create_commit(
+    octocrab: &Arc<Octocrab>,
+    owner: &str,
+    repo: &str,
+    message: &str,
+    tree: &str,
+    parent: &str,
+) -> octocrab::Result<GitObject> {
+    let body = CreateCommitBody {
+        message: message.to_string(),
+        tree: tree.to_string(),
+        parents: vec![parent.to_string()],
+    };
+
+    let route = format!(
+        "repos/{owner}/{repo}/git/commits",
+        owner = owner,
+        repo = repo
+    );
+
+    octocrab.post(route, Some(&body)).await
+}
+
+#[derive(Debug, Serialize)]
+struct UpdateRefBody {
+    sha: String,
+    force: bool,
+}
+
+async fn update_branch(
+    octocrab: &Arc<Octocrab>,
+    owner: &str,
+    repo: &str,
+    branch: &str,
+    sha: &str,
+    force: bool,
+) -> octocrab::Result<octocrab::models::repos::Ref> {
+    let body = UpdateRefBody {
         sha: sha.to_string(),
+        force,
     };
 
     let route = format!(
-        "/repos/{owner}/{repo}/contents/{path}",
+        "repos/{owner}/{repo}/git/refs/heads/{branch}",
         owner = owner,
         repo = repo,
-        path = path
+        branch = branch
     );
 
-    octocrab
-        .put::<UpdateFileResponse, _, _>(route, Some(&body))
-        .await
-        .unwrap();
+    octocrab.patch(route, Some(&body)).await
 }
 
 fn get_pull_request_name(owner: &str, repo: &str, source_branch: &str) -> String {
@@ -501,9 +1128,11 @@ fn get_pull_request_name(owner: &str, repo: &str, source_branch: &str) -> String
     )
 }
 
-fn get_pull_request_body(owner: &str, repo: &str, source_branch: &str) -> String {
+/// `web_url` is the web root of the source, e.g. `https://github.com`.
+fn get_pull_request_body(web_url: &str, owner: &str, repo: &str, source_branch: &str) -> String {
     let link = format!(
-        "https://github.com/{owner}/{repo}/{branch}",
+        "{web_url}/{owner}/{repo}/{branch}",
+        web_url = web_url.trim_end_matches('/'),
         owner = owner,
         repo = repo,
         branch = source_branch
@@ -517,28 +1146,153 @@ fn get_pull_request_body(owner: &str, repo: &str, source_branch: &str) -> String
     )
 }
 
-async fn create_pull_request(
+#[derive(Debug, Deserialize, PartialEq)]
+struct PullRequestInfo {
+    number: u64,
+}
+
+async fn get_open_pull_requests(
     octocrab: &Arc<Octocrab>,
     owner: &str,
     repo: &str,
-    source_owner: &str,
-    source_repo: &str,
-    source_branch: &str,
     destination_branch_name: &str,
-    base_ref: &str,
-) -> Result<octocrab::models::pulls::PullRequest, octocrab::Error> {
+) -> octocrab::Result<Vec<PullRequestInfo>> {
+    let route = format!("repos/{owner}/{repo}/pulls", owner = owner, repo = repo);
+    let head = format!(
+        "{owner}:{branch}",
+        owner = owner,
+        branch = destination_branch_name
+    );
+
     octocrab
-        .pulls(owner, repo)
-        .create(
-            get_pull_request_name(source_owner, source_repo, source_branch),
-            destination_branch_name,
-            base_ref,
-        )
-        .body(get_pull_request_body(
-            source_owner,
-            source_repo,
-            source_branch,
-        ))
-        .send()
+        .get(route, Some(&[("state", "open"), ("head", &head)]))
         .await
 }
+
+fn get_pull_request_title(config: &ParsedConfig) -> String {
+    let source = &config.source;
+
+    config
+        .pull_request
+        .title
+        .clone()
+        .unwrap_or_else(|| get_pull_request_name(&source.owner, &source.name, &source.git_ref))
+}
+
+#[derive(Debug, Serialize)]
+struct CreatePullRequestBody {
+    title: String,
+    body: String,
+    head: String,
+    base: String,
+    draft: bool,
+}
+
+async fn create_pull_request(
+    octocrab: &Arc<Octocrab>,
+    owner: &str,
+    repo: &str,
+    config: &ParsedConfig,
+    web_url: &str,
+    destination_branch_name: &str,
+    base_ref: &str,
+) -> octocrab::Result<PullRequestInfo> {
+    let body = CreatePullRequestBody {
+        title: get_pull_request_title(config),
+        body: get_pull_request_body(
+            web_url,
+            &config.source.owner,
+            &config.source.name,
+            &config.source.git_ref,
+        ),
+        head: destination_branch_name.to_string(),
+        base: base_ref.to_string(),
+        draft: config.pull_request.draft.unwrap_or_default(),
+    };
+
+    let route = format!("repos/{owner}/{repo}/pulls", owner = owner, repo = repo);
+
+    octocrab.post(route, Some(&body)).await
+}
+
+#[derive(Debug, Serialize)]
+struct UpdatePullRequestBody {
+    title: String,
+    body: String,
+}
+
+async fn update_pull_request(
+    octocrab: &Arc<Octocrab>,
+    owner: &str,
+    repo: &str,
+    config: &ParsedConfig,
+    web_url: &str,
+    number: u64,
+) -> octocrab::Result<PullRequestInfo> {
+    let body = UpdatePullRequestBody {
+        title: get_pull_request_title(config),
+        body: get_pull_request_body(
+            web_url,
+            &config.source.owner,
+            &config.source.name,
+            &config.source.git_ref,
+        ),
+    };
+
+    let route = format!(
+        "repos/{owner}/{repo}/pulls/{number}",
+        owner = owner,
+        repo = repo,
+        number = number
+    );
+
+    octocrab.patch(route, Some(&body)).await
+}
+
+#[derive(Debug, Serialize)]
+struct ClosePullRequestBody {
+    state: String,
+}
+
+async fn close_pull_request(
+    octocrab: &Arc<Octocrab>,
+    owner: &str,
+    repo: &str,
+    number: u64,
+) -> octocrab::Result<PullRequestInfo> {
+    let body = ClosePullRequestBody {
+        state: "closed".to_string(),
+    };
+
+    let route = format!(
+        "repos/{owner}/{repo}/pulls/{number}",
+        owner = owner,
+        repo = repo,
+        number = number
+    );
+
+    octocrab.patch(route, Some(&body)).await
+}
+
+#[derive(Debug, Serialize)]
+struct AddLabelsBody<'a> {
+    labels: &'a [String],
+}
+
+/// Adds `labels` to the pull request, keeping the labels it already has.
+async fn add_labels(
+    octocrab: &Arc<Octocrab>,
+    owner: &str,
+    repo: &str,
+    number: u64,
+    labels: &[String],
+) -> octocrab::Result<Vec<serde_json::Value>> {
+    let route = format!(
+        "repos/{owner}/{repo}/issues/{number}/labels",
+        owner = owner,
+        repo = repo,
+        number = number
+    );
+
+    octocrab.post(route, Some(&AddLabelsBody { labels })).await
+}
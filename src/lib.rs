@@ -1,6 +1,10 @@
 pub mod cli;
+pub mod error;
 pub mod event;
 pub mod fixtures;
 pub mod git_tree;
+pub mod github_clients;
 pub mod github_provider;
+pub mod plan;
 pub mod provider;
+pub mod summary;
@@ -1,80 +1,491 @@
-use event::Event;
-use git_tree::GitTree;
+use std::{process, sync::Arc};
+
+use cli::{Args, ConnectionConfig, DestinationRepository};
+use error::Result;
+use futures::{stream, StreamExt};
+use git_tree::{GitTree, Tree};
+use github_clients::GithubClients;
 use github_provider::GithubProvider;
+use octocrab::Octocrab;
 use provider::Provider;
+use summary::SyncStatus;
 
 pub mod cli;
+pub mod error;
 pub mod event;
 pub mod fixtures;
 pub mod git_tree;
+pub mod github_clients;
 pub mod github_provider;
+pub mod plan;
 pub mod provider;
+pub mod summary;
 
 #[tokio::main]
 async fn main() {
-    let config = cli::run().unwrap();
+    let (args, jobs) = cli::run().unwrap_or_else(|error| {
+        eprintln!("error: {}", error);
+        process::exit(1);
+    });
+
+    // shared by the jobs, so jobs on the same connection reuse its client
+    let clients = GithubClients::default();
+
+    let mut has_failures = false;
+    for (index, job) in jobs.into_iter().enumerate() {
+        let github_provider = github_provider::GithubProvider::new(job);
+
+        // printed first, so the plans of --dry-run and --verbose show up under it
+        if let Some(name) = &github_provider.config.job {
+            if index > 0 {
+                println!();
+            }
+            println!("job {}", name);
+        }
 
-    let github_provider = github_provider::GithubProvider { config };
+        let results = if args.reverse {
+            sync_upstream(&args, &github_provider, &clients).await
+        } else {
+            sync_downstream(&args, &github_provider, &clients).await
+        };
 
-    let instance = github_provider.configure_provider(None);
+        // a failure reason may echo a token, e.g. from an error response
+        print!(
+            "{}",
+            github_provider
+                .config
+                .redact(&summary::format_summary(&results))
+        );
 
-    let source_tree = github_provider.create_source_tree(instance.clone()).await;
+        has_failures |= summary::has_failures(&results);
+    }
+
+    if has_failures {
+        process::exit(1);
+    }
+}
+
+async fn sync_downstream(
+    args: &Args,
+    github_provider: &GithubProvider,
+    clients: &GithubClients,
+) -> Vec<(DestinationRepository, SyncStatus)> {
+    let source = &github_provider.config.source;
+    let source_result = async {
+        let instance = get_instance(
+            clients,
+            github_provider,
+            &source.connection,
+            &source.owner,
+            &source.name,
+        )
+        .await?;
+
+        github_provider.fetch_source_tree(instance).await
+    }
+    .await;
+
+    // the other jobs still run, so every destination of this one fails instead
+    let source_tree = match source_result {
+        Ok(source) => source,
+        Err(error) => {
+            let reason = error.to_string();
+            let status = SyncStatus::Failed(format!(
+                "{}/{}: {}",
+                source.owner,
+                source.name,
+                reason.lines().next().unwrap_or_default()
+            ));
+
+            return github_provider
+                .config
+                .destinations
+                .iter()
+                .map(|destination| (destination.clone(), status.clone()))
+                .collect();
+        }
+    };
 
     let destination_branch_name = github_provider.get_destination_branch();
 
-    for destination in github_provider.config.destinations.iter() {
-        let destination_tree = github_provider
-            .create_destination_tree(instance.clone(), &destination)
-            .await;
+    stream::iter(github_provider.config.destinations.iter())
+        .map(|destination| async {
+            let status = async {
+                let destination_provider = github_provider
+                    .with_config(github_provider.config.for_destination(destination)?);
+
+                // fetched again, the token of the source may have expired by now
+                let source_instance = get_instance(
+                    clients,
+                    github_provider,
+                    &source.connection,
+                    &source.owner,
+                    &source.name,
+                )
+                .await?;
+                let destination_source_tree = destination_provider
+                    .transform_source_tree(source_instance, source_tree.clone())
+                    .await?;
 
+                sync_destination(
+                    args,
+                    &destination_provider,
+                    clients,
+                    &destination_source_tree,
+                    destination,
+                    &destination_branch_name,
+                )
+                .await
+            }
+            .await
+            .unwrap_or_else(|error| {
+                let reason = error.to_string();
+                SyncStatus::Failed(reason.lines().next().unwrap_or_default().to_string())
+            });
+
+            (destination.clone(), status)
+        })
+        .buffered(github_provider.config.max_concurrency)
+        .collect()
+        .await
+}
+
+/// Syncs every destination back to the source, each destination opening its
+/// own pull request against the source repository.
+async fn sync_upstream(
+    args: &Args,
+    github_provider: &GithubProvider,
+    clients: &GithubClients,
+) -> Vec<(DestinationRepository, SyncStatus)> {
+    stream::iter(github_provider.config.destinations.iter())
+        .map(|destination| async {
+            let status = reverse_destination(args, github_provider, clients, destination)
+                .await
+                .unwrap_or_else(|error| {
+                    let reason = error.to_string();
+                    SyncStatus::Failed(reason.lines().next().unwrap_or_default().to_string())
+                });
+
+            (destination.clone(), status)
+        })
+        .buffered(github_provider.config.max_concurrency)
+        .collect()
+        .await
+}
+
+async fn reverse_destination(
+    args: &Args,
+    github_provider: &GithubProvider,
+    clients: &GithubClients,
+    destination: &DestinationRepository,
+) -> Result<SyncStatus> {
+    let instance = get_instance(
+        clients,
+        github_provider,
+        &destination.connection,
+        &destination.owner,
+        &destination.name,
+    )
+    .await?;
+    let base_branch = github_provider
+        .get_destination_base_branch(instance.clone(), destination)
+        .await?;
+
+    let reversed_provider = github_provider.with_config(
         github_provider
-            .create_destination_branch(instance.clone(), &destination, &destination_branch_name)
-            .await;
+            .config
+            .for_destination(destination)?
+            .reverse(destination, &base_branch)?,
+    );
 
-        let events = source_tree.generate_events(&destination_tree);
-
-        for event in events.iter() {
-            match &event {
-                Event::Create { path, content } => {
-                    GithubProvider::create_file(
-                        instance.clone(),
-                        &destination,
-                        &path,
-                        content,
-                        &destination_branch_name,
-                    )
-                    .await;
-                }
-                Event::Update { path, content, sha } => {
-                    GithubProvider::update_file(
-                        instance.clone(),
-                        &destination,
-                        path,
-                        content,
-                        sha,
-                        &destination_branch_name,
-                    )
-                    .await;
-                }
-                Event::Delete { path, sha } => {
-                    GithubProvider::delete_file(
-                        instance.clone(),
-                        &destination,
-                        &path,
-                        &sha,
-                        &destination_branch_name,
-                    )
-                    .await;
-                }
-            };
-        }
+    let source_tree = reversed_provider.create_source_tree(instance).await?;
+
+    let upstream = &reversed_provider.config.destinations[0];
+
+    sync_destination(
+        args,
+        &reversed_provider,
+        clients,
+        &source_tree,
+        upstream,
+        &reversed_provider.get_destination_branch(),
+    )
+    .await
+}
+
+/// Minting an installation token takes requests too, so it counts against
+/// the request limit of the provider.
+async fn get_instance(
+    clients: &GithubClients,
+    github_provider: &GithubProvider,
+    connection: &ConnectionConfig,
+    owner: &str,
+    repo: &str,
+) -> Result<Arc<Octocrab>> {
+    let connection = github_provider.config.get_connection(connection);
+
+    github_provider
+        .requests
+        .run(clients.get(&connection, owner, repo))
+        .await
+}
+
+async fn sync_destination(
+    args: &Args,
+    github_provider: &GithubProvider,
+    clients: &GithubClients,
+    source_tree: &Tree,
+    destination: &DestinationRepository,
+    destination_branch_name: &str,
+) -> Result<SyncStatus> {
+    // fetched for every step, so an installation token expiring in the middle
+    // of a destination is replaced in time
+    let instance = || {
+        get_instance(
+            clients,
+            github_provider,
+            &destination.connection,
+            &destination.owner,
+            &destination.name,
+        )
+    };
+
+    let base_branch = github_provider
+        .get_destination_base_branch(instance().await?, destination)
+        .await?;
 
+    let destination_tree = github_provider
+        .create_destination_tree(instance().await?, destination, &base_branch)
+        .await?;
+
+    let events = source_tree
+        .clone()
+        .apply_update_fns(&destination_tree, &github_provider.config.update_fns)
+        .generate_events(&destination_tree);
+
+    if args.dry_run || args.verbose {
+        // the synced files may contain a token too
+        let config = &github_provider.config;
+        print!(
+            "{}",
+            config.redact(&plan::format_plan(destination, &events))
+        );
+        print!(
+            "{}",
+            config.redact(&plan::format_diff(&events, &destination_tree))
+        );
+    }
+
+    if args.dry_run {
+        return Ok(SyncStatus::Skipped("dry run".to_string()));
+    }
+
+    if events.is_empty() {
+        let closed = github_provider
+            .close_pull_request_destination(instance().await?, destination, destination_branch_name)
+            .await?;
+
+        return Ok(SyncStatus::Skipped(match closed {
+            Some(number) => format!("no changes, closed stale pull request #{}", number),
+            None => "no changes".to_string(),
+        }));
+    }
+
+    let tree_sha = github_provider
+        .create_commit_tree(instance().await?, destination, &events, &base_branch)
+        .await?;
+
+    let updated = github_provider
+        .create_destination_branch(
+            instance().await?,
+            destination,
+            &base_branch,
+            destination_branch_name,
+            &tree_sha,
+        )
+        .await?;
+
+    if updated {
         github_provider
-            .create_pull_request_destination(
-                instance.clone(),
-                &destination,
-                &destination_branch_name,
+            .create_commit(
+                instance().await?,
+                destination,
+                &tree_sha,
+                destination_branch_name,
+            )
+            .await?;
+    }
+
+    github_provider
+        .create_pull_request_destination(
+            instance().await?,
+            destination,
+            &base_branch,
+            destination_branch_name,
+        )
+        .await?;
+
+    Ok(SyncStatus::Succeeded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cli::parser::TemplateArgs;
+    use cli::{Credentials, GithubApp, ParsedConfig, SourceRepository, Transformation};
+    use fixtures::content::{get_blob_json, get_tree_entry_json};
+    use fixtures::workdir_path::create_workdir_path;
+    use serde_json::json;
+    use std::{collections::HashMap, path::PathBuf};
+    use wiremock::matchers::{header, method, path, path_regex, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn get_destination(name: &str) -> DestinationRepository {
+        DestinationRepository {
+            owner: "owner".to_string(),
+            name: name.to_string(),
+            git_ref: Some("main".to_string()),
+            connection: Default::default(),
+            overrides: Default::default(),
+        }
+    }
+
+    /// Only answers requests authenticated with `token`.
+    fn get_tree_with_token_mock(repo: &str, token: &str, entries: Vec<serde_json::Value>) -> Mock {
+        Mock::given(method("GET"))
+            .and(path(format!("/repos/owner/{}/git/trees/main", repo)))
+            .and(query_param("recursive", "1"))
+            .and(header(
+                "Authorization",
+                format!("Bearer {}", token).as_str(),
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "sha": "tree_sha",
+                "tree": entries,
+                "truncated": false,
+            })))
+            .expect(1)
+    }
+
+    #[tokio::test]
+    async fn refreshes_expired_token_after_the_source_tree() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path_regex(r"^/repos/owner/[^/]+/installation$"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "id": 1 })))
+            .mount(&mock_server)
+            .await;
+        // the first token has expired by the time the destinations are synced
+        Mock::given(method("POST"))
+            .and(path("/app/installations/1/access_tokens"))
+            .respond_with(ResponseTemplate::new(201).set_body_json(json!({
+                "token": "ghs_expired",
+                "expires_at": "2000-01-01T00:00:00Z",
+            })))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/app/installations/1/access_tokens"))
+            .respond_with(ResponseTemplate::new(201).set_body_json(json!({
+                "token": "ghs_fresh",
+                "expires_at": "2999-01-01T00:00:00Z",
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        get_tree_with_token_mock(
+            "repo1",
+            "ghs_expired",
+            vec![get_tree_entry_json("README.md", "sha1")],
+        )
+        .mount(&mock_server)
+        .await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo1/git/blobs/sha1"))
+            .and(header("Authorization", "Bearer ghs_expired"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(get_blob_json("sha1", "{{source.sha}}")),
             )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo1/commits/main"))
+            .and(header("Authorization", "Bearer ghs_fresh"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "sha": "source_sha" })))
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+        get_tree_with_token_mock("repo2", "ghs_fresh", vec![])
+            .mount(&mock_server)
             .await;
+        get_tree_with_token_mock("repo3", "ghs_fresh", vec![])
+            .mount(&mock_server)
+            .await;
+
+        let config = ParsedConfig {
+            version: "0.1".to_string(),
+            source: SourceRepository {
+                owner: "owner".to_string(),
+                name: "repo1".to_string(),
+                git_ref: "main".to_string(),
+                connection: Default::default(),
+            },
+            destinations: vec![get_destination("repo2"), get_destination("repo3")],
+            credentials: Credentials::App(GithubApp {
+                id: 42,
+                private_key_path: PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+                    .join("tests/integration/fixtures/app_private_key.pem"),
+            }),
+            api_url: Some(mock_server.uri()),
+            web_url: None,
+            destination_files: create_workdir_path(""),
+            origin_files: create_workdir_path(""),
+            transformations: Some(vec![Transformation::Template {
+                args: TemplateArgs {
+                    variables: HashMap::new(),
+                },
+                paths: None,
+            }]),
+            update_fns: None,
+            max_concurrency: 1,
+            pull_request: Default::default(),
+            job: None,
+        };
+        let args = Args {
+            config: PathBuf::from("syncy.yml"),
+            dry_run: true,
+            job: vec![],
+            max_concurrency: None,
+            reverse: false,
+            token: None,
+            verbose: false,
+        };
+
+        let results = sync_downstream(
+            &args,
+            &GithubProvider::new(config),
+            &GithubClients::default(),
+        )
+        .await;
+
+        assert_eq!(
+            results,
+            vec![
+                (
+                    get_destination("repo2"),
+                    SyncStatus::Skipped("dry run".to_string())
+                ),
+                (
+                    get_destination("repo3"),
+                    SyncStatus::Skipped("dry run".to_string())
+                ),
+            ]
+        );
+        mock_server.verify().await;
     }
 }
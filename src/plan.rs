@@ -0,0 +1,260 @@
+use similar::TextDiff;
+
+use crate::{
+    cli::DestinationRepository,
+    event::Event,
+    git_tree::{as_text, Tree},
+};
+
+/// Renders the events planned for a destination as a diff-like list,
+/// one line per file ordered by path.
+pub fn format_plan(destination: &DestinationRepository, events: &[Event]) -> String {
+    let mut plan = format!(
+        "{owner}/{repo}:\n",
+        owner = destination.owner,
+        repo = destination.name
+    );
+
+    if events.is_empty() {
+        plan.push_str("  no changes\n");
+        return plan;
+    }
+
+    let mut sorted_events: Vec<&Event> = events.iter().collect();
+    sorted_events.sort_by(|a, b| a.path().cmp(b.path()));
+
+    for event in sorted_events {
+        plan.push_str(&format!("  {}\n", event));
+    }
+
+    plan
+}
+
+/// Renders the events as a unified diff against the destination tree: updates
+/// are diffed against the current destination content, created files are
+/// diffed against `/dev/null` and deleted files are diffed to `/dev/null`.
+/// Binary files are only reported as changed and mode changes are reported
+/// before the content diff, like `git diff` does.
+pub fn format_diff(events: &[Event], destination_tree: &Tree) -> String {
+    let mut sorted_events: Vec<&Event> = events.iter().collect();
+    sorted_events.sort_by(|a, b| a.path().cmp(b.path()));
+
+    let mut diff = String::new();
+    for event in sorted_events {
+        let (old_content, new_content, old_header, new_header) = match event {
+            Event::Create { path, content, .. } => (
+                &[][..],
+                content.as_deref().unwrap_or_default(),
+                "/dev/null".to_string(),
+                format!("b/{}", path),
+            ),
+            Event::Update {
+                path,
+                content,
+                kind,
+                ..
+            } => {
+                if let Some(destination_node) = destination_tree.get(path) {
+                    if destination_node.kind != *kind {
+                        diff.push_str(&format!(
+                            "old mode {}\nnew mode {}\n",
+                            destination_node.kind.mode(),
+                            kind.mode()
+                        ));
+                    }
+                }
+
+                (
+                    get_destination_content(destination_tree, path),
+                    content.as_deref().unwrap_or_default(),
+                    format!("a/{}", path),
+                    format!("b/{}", path),
+                )
+            }
+            Event::Delete { path, .. } => (
+                get_destination_content(destination_tree, path),
+                &[][..],
+                format!("a/{}", path),
+                "/dev/null".to_string(),
+            ),
+        };
+
+        let (old_content, new_content) = match (as_text(old_content), as_text(new_content)) {
+            (Some(old_content), Some(new_content)) => (old_content, new_content),
+            _ => {
+                diff.push_str(&format!(
+                    "Binary files {} and {} differ\n",
+                    old_header, new_header
+                ));
+                continue;
+            }
+        };
+
+        diff.push_str(
+            &TextDiff::from_lines(old_content, new_content)
+                .unified_diff()
+                .header(&old_header, &new_header)
+                .to_string(),
+        );
+    }
+
+    diff
+}
+
+fn get_destination_content<'a>(destination_tree: &'a Tree, path: &str) -> &'a [u8] {
+    destination_tree
+        .get(path)
+        .and_then(|node| node.content.as_deref())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_diff, format_plan};
+    use crate::{
+        cli::DestinationRepository,
+        event::Event,
+        git_tree::{Node, NodeKind, Tree},
+    };
+    use indoc::indoc;
+
+    #[test]
+    fn test_format_plan() {
+        let destination = DestinationRepository {
+            owner: "owner".to_string(),
+            name: "repo2".to_string(),
+            git_ref: None,
+            connection: Default::default(),
+            overrides: Default::default(),
+        };
+        let events = vec![
+            Event::Delete {
+                path: "folder/c".to_string(),
+                sha: "".to_string(),
+            },
+            Event::Create {
+                path: "folder/a".to_string(),
+                content: None,
+                kind: NodeKind::Blob,
+            },
+            Event::Update {
+                path: "folder/b".to_string(),
+                content: None,
+                kind: NodeKind::Blob,
+                sha: "".to_string(),
+            },
+        ];
+
+        let expected = indoc! {"
+            owner/repo2:
+              + folder/a
+              ~ folder/b
+              - folder/c
+        "};
+
+        assert_eq!(format_plan(&destination, &events), expected);
+    }
+
+    #[test]
+    fn test_format_plan_without_changes() {
+        let destination = DestinationRepository {
+            owner: "owner".to_string(),
+            name: "repo2".to_string(),
+            git_ref: None,
+            connection: Default::default(),
+            overrides: Default::default(),
+        };
+
+        assert_eq!(
+            format_plan(&destination, &[]),
+            "owner/repo2:\n  no changes\n"
+        );
+    }
+
+    #[test]
+    fn test_format_diff() {
+        let destination_tree = Tree::from([
+            (
+                "folder/b".to_string(),
+                Node {
+                    path: "folder/b".to_string(),
+                    content: Some(b"line1\nline2\n".to_vec()),
+                    kind: NodeKind::Blob,
+                    git_url: "".to_string(),
+                    sha: "".to_string(),
+                },
+            ),
+            (
+                "folder/c".to_string(),
+                Node {
+                    path: "folder/c".to_string(),
+                    content: Some(b"removed\n".to_vec()),
+                    kind: NodeKind::Blob,
+                    git_url: "".to_string(),
+                    sha: "".to_string(),
+                },
+            ),
+        ]);
+        let events = vec![
+            Event::Delete {
+                path: "folder/c".to_string(),
+                sha: "".to_string(),
+            },
+            Event::Update {
+                path: "folder/b".to_string(),
+                content: Some(b"line1\nchanged\n".to_vec()),
+                kind: NodeKind::Blob,
+                sha: "".to_string(),
+            },
+            Event::Create {
+                path: "folder/a".to_string(),
+                content: Some(b"added\n".to_vec()),
+                kind: NodeKind::Blob,
+            },
+        ];
+
+        let expected = indoc! {"
+            --- /dev/null
+            +++ b/folder/a
+            @@ -0,0 +1 @@
+            +added
+            --- a/folder/b
+            +++ b/folder/b
+            @@ -1,2 +1,2 @@
+             line1
+            -line2
+            +changed
+            --- a/folder/c
+            +++ /dev/null
+            @@ -1 +0,0 @@
+            -removed
+        "};
+
+        assert_eq!(format_diff(&events, &destination_tree), expected);
+    }
+
+    #[test]
+    fn test_format_diff_binary() {
+        let destination_tree = Tree::from([(
+            "image.png".to_string(),
+            Node {
+                path: "image.png".to_string(),
+                content: Some(b"\x89PNG\x00\x01".to_vec()),
+                kind: NodeKind::Blob,
+                git_url: "".to_string(),
+                sha: "".to_string(),
+            },
+        )]);
+        let events = vec![Event::Update {
+            path: "image.png".to_string(),
+            content: Some(b"\x89PNG\x00\x02".to_vec()),
+            kind: NodeKind::Blob,
+            sha: "".to_string(),
+        }];
+
+        assert_eq!(
+            format_diff(&events, &destination_tree),
+            "Binary files a/image.png and b/image.png differ\n"
+        );
+    }
+}
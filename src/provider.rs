@@ -1,57 +1,75 @@
 use async_trait::async_trait;
 
-use crate::{cli::DestinationRepository, git_tree};
+use crate::{cli::DestinationRepository, error::Result, event::Event, git_tree};
 
 #[async_trait]
 pub trait Provider<T> {
-    fn configure_provider(&self, base_url: Option<String>) -> T;
+    fn configure_provider(&self, base_url: Option<String>) -> Result<T>;
 
-    async fn create_source_tree(&self, instance: T) -> git_tree::Tree;
+    async fn create_source_tree(&self, instance: T) -> Result<git_tree::Tree>;
 
+    /// Returns the branch the destination is synced against: the configured
+    /// `git_ref` or, when it is not set, the default branch of the repository.
+    async fn get_destination_base_branch(
+        &self,
+        instance: T,
+        destination: &DestinationRepository,
+    ) -> Result<String>;
+
+    /// Resets `destination_branch_name` to the base branch, creating it when
+    /// missing. Returns `false` and leaves the branch alone when its head
+    /// already has `tree_sha`, so an open pull request isn't pushed again.
     async fn create_destination_branch(
         &self,
         instance: T,
         destination: &DestinationRepository,
+        base_branch: &str,
         destination_branch_name: &str,
-    ) -> ();
+        tree_sha: &str,
+    ) -> Result<bool>;
 
     async fn create_destination_tree(
         &self,
         instance: T,
         destination: &DestinationRepository,
-    ) -> git_tree::Tree;
+        base_branch: &str,
+    ) -> Result<git_tree::Tree>;
 
-    async fn create_file(
+    /// Creates the tree of the base branch with `events` applied and returns
+    /// its sha.
+    async fn create_commit_tree(
+        &self,
         instance: T,
         destination: &DestinationRepository,
-        path: &str,
-        content: &Option<String>,
-        destination_branch_name: &str,
-    ) -> ();
+        events: &[Event],
+        base_branch: &str,
+    ) -> Result<String>;
 
-    async fn update_file(
+    async fn create_commit(
+        &self,
         instance: T,
         destination: &DestinationRepository,
-        path: &str,
-        content: &Option<String>,
-        sha: &str,
+        tree_sha: &str,
         destination_branch_name: &str,
-    ) -> ();
+    ) -> Result<()>;
 
-    async fn delete_file(
+    fn get_destination_branch(&self) -> String;
+
+    async fn create_pull_request_destination(
+        &self,
         instance: T,
         destination: &DestinationRepository,
-        path: &str,
-        sha: &str,
+        base_branch: &str,
         destination_branch_name: &str,
-    ) -> ();
+    ) -> Result<()>;
 
-    fn get_destination_branch(&self) -> String;
-
-    async fn create_pull_request_destination(
+    /// Closes the pull request left open on `destination_branch_name` and
+    /// returns its number. Once the destination matches the source, merging it
+    /// would bring back changes the source has since reverted.
+    async fn close_pull_request_destination(
         &self,
         instance: T,
         destination: &DestinationRepository,
         destination_branch_name: &str,
-    ) -> ();
+    ) -> Result<Option<u64>>;
 }
@@ -0,0 +1,119 @@
+use crate::cli::DestinationRepository;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SyncStatus {
+    Succeeded,
+    Skipped(String),
+    Failed(String),
+}
+
+impl SyncStatus {
+    fn label(&self) -> &str {
+        match self {
+            SyncStatus::Succeeded => "succeeded",
+            SyncStatus::Skipped(_) => "skipped",
+            SyncStatus::Failed(_) => "failed",
+        }
+    }
+
+    fn reason(&self) -> &str {
+        match self {
+            SyncStatus::Succeeded => "",
+            SyncStatus::Skipped(reason) | SyncStatus::Failed(reason) => reason,
+        }
+    }
+}
+
+/// Renders the outcome of every destination as an aligned table.
+pub fn format_summary(results: &[(DestinationRepository, SyncStatus)]) -> String {
+    let rows: Vec<(String, &str, &str)> = results
+        .iter()
+        .map(|(destination, status)| {
+            (
+                format!("{}/{}", destination.owner, destination.name),
+                status.label(),
+                status.reason(),
+            )
+        })
+        .collect();
+
+    let destination_width = rows
+        .iter()
+        .map(|(destination, _, _)| destination.len())
+        .chain(std::iter::once("destination".len()))
+        .max()
+        .unwrap_or_default();
+    let status_width = "succeeded".len();
+
+    let mut summary = format!(
+        "{:<destination_width$}  {:<status_width$}  reason\n",
+        "destination",
+        "status",
+        destination_width = destination_width,
+        status_width = status_width
+    );
+
+    for (destination, status, reason) in rows {
+        let line = format!(
+            "{:<destination_width$}  {:<status_width$}  {}",
+            destination,
+            status,
+            reason,
+            destination_width = destination_width,
+            status_width = status_width
+        );
+        summary.push_str(line.trim_end());
+        summary.push('\n');
+    }
+
+    summary
+}
+
+pub fn has_failures(results: &[(DestinationRepository, SyncStatus)]) -> bool {
+    results
+        .iter()
+        .any(|(_, status)| matches!(status, SyncStatus::Failed(_)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_summary, has_failures, SyncStatus};
+    use crate::cli::DestinationRepository;
+    use indoc::indoc;
+
+    fn destination(name: &str) -> DestinationRepository {
+        DestinationRepository {
+            owner: "owner".to_string(),
+            name: name.to_string(),
+            git_ref: None,
+            connection: Default::default(),
+            overrides: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_format_summary() {
+        let results = vec![
+            (destination("repo2"), SyncStatus::Succeeded),
+            (
+                destination("repository3"),
+                SyncStatus::Failed("github request failed: Not Found".to_string()),
+            ),
+            (
+                destination("repo4"),
+                SyncStatus::Skipped("no changes".to_string()),
+            ),
+        ];
+
+        let expected = indoc! {"
+            destination        status     reason
+            owner/repo2        succeeded
+            owner/repository3  failed     github request failed: Not Found
+            owner/repo4        skipped    no changes
+        "};
+
+        assert_eq!(format_summary(&results), expected);
+        assert!(has_failures(&results));
+        assert!(!has_failures(&results[..1]));
+    }
+}
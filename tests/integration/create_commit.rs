@@ -0,0 +1,317 @@
+use crate::mocks::github::{
+    create_blob_mock, create_commit_mock, create_tree_mock, get_commit_mock, get_ref_mock,
+    not_found_mock, update_ref_conflict_mock, update_ref_mock,
+};
+use serde_json::json;
+use syncy::fixtures::workdir_path::create_workdir_path;
+use syncy::{
+    cli::{
+        Credentials, DestinationRepository, ParsedConfig, SourceRepository, Token,
+        DEFAULT_MAX_CONCURRENCY,
+    },
+    error::Error,
+    event::Event,
+    git_tree::NodeKind,
+    github_provider::GithubProvider,
+    provider::Provider,
+};
+use wiremock::MockServer;
+
+#[tokio::test]
+async fn create_commit_success() {
+    let destination_repository = DestinationRepository {
+        owner: "owner".to_string(),
+        name: "repo2".to_string(),
+        git_ref: None,
+        connection: Default::default(),
+        overrides: Default::default(),
+    };
+    let config = ParsedConfig {
+        version: "0.1".to_string(),
+        source: SourceRepository {
+            owner: "owner".to_string(),
+            name: "repo1".to_string(),
+            git_ref: "main".to_string(),
+            connection: Default::default(),
+        },
+        destinations: vec![destination_repository.clone()],
+        credentials: Credentials::Token(Token::from("random_token")),
+        api_url: None,
+        web_url: None,
+        destination_files: create_workdir_path(""),
+        origin_files: create_workdir_path(""),
+        transformations: None,
+        update_fns: None,
+        max_concurrency: DEFAULT_MAX_CONCURRENCY,
+        pull_request: Default::default(),
+        job: None,
+    };
+    let owner = &destination_repository.owner;
+    let repo = &destination_repository.name;
+    let branch = "syncy/owner/repo1";
+
+    let mock_server = MockServer::start().await;
+
+    get_ref_mock(owner, repo, "main", "base_sha")
+        .mount(&mock_server)
+        .await;
+    get_commit_mock(owner, repo, "base_sha", "base_tree_sha")
+        .mount(&mock_server)
+        .await;
+    get_ref_mock(owner, repo, branch, "parent_sha")
+        .mount(&mock_server)
+        .await;
+    create_blob_mock(owner, repo, "created", "created_blob_sha")
+        .mount(&mock_server)
+        .await;
+    create_blob_mock(owner, repo, "updated", "updated_blob_sha")
+        .mount(&mock_server)
+        .await;
+    create_tree_mock(
+        owner,
+        repo,
+        json!({
+            "base_tree": "base_tree_sha",
+            "tree": [
+                { "path": "folder/test1", "mode": "100644", "type": "blob", "sha": "created_blob_sha" },
+                { "path": "folder/test2", "mode": "100644", "type": "blob", "sha": "updated_blob_sha" },
+                { "path": "folder/test3", "mode": "100644", "type": "blob", "sha": null },
+            ],
+        }),
+        "tree_sha",
+    )
+    .mount(&mock_server)
+    .await;
+    create_commit_mock(
+        owner,
+        repo,
+        json!({
+            "message": "Update from owner/repo1 branch: main",
+            "tree": "tree_sha",
+            "parents": ["parent_sha"],
+        }),
+        "commit_sha",
+    )
+    .mount(&mock_server)
+    .await;
+    update_ref_mock(owner, repo, branch, "commit_sha", false)
+        .mount(&mock_server)
+        .await;
+
+    let events = vec![
+        Event::Create {
+            path: "folder/test1".to_string(),
+            content: Some(b"created".to_vec()),
+            kind: NodeKind::Blob,
+        },
+        Event::Update {
+            path: "folder/test2".to_string(),
+            content: Some(b"updated".to_vec()),
+            kind: NodeKind::Blob,
+            sha: "old_sha".to_string(),
+        },
+        Event::Delete {
+            path: "folder/test3".to_string(),
+            sha: "deleted_sha".to_string(),
+        },
+    ];
+
+    let github_provider = GithubProvider::new(config);
+
+    let instance = github_provider
+        .configure_provider(Some(mock_server.uri()))
+        .unwrap();
+
+    let tree_sha = github_provider
+        .create_commit_tree(instance.clone(), &destination_repository, &events, "main")
+        .await
+        .unwrap();
+    github_provider
+        .create_commit(instance, &destination_repository, &tree_sha, branch)
+        .await
+        .unwrap();
+
+    assert_eq!(tree_sha, "tree_sha");
+    mock_server.verify().await;
+}
+
+fn get_config(destination: &DestinationRepository) -> ParsedConfig {
+    ParsedConfig {
+        version: "0.1".to_string(),
+        source: SourceRepository {
+            owner: "owner".to_string(),
+            name: "repo1".to_string(),
+            git_ref: "main".to_string(),
+            connection: Default::default(),
+        },
+        destinations: vec![destination.clone()],
+        credentials: Credentials::Token(Token::from("random_token")),
+        api_url: None,
+        web_url: None,
+        destination_files: create_workdir_path(""),
+        origin_files: create_workdir_path(""),
+        transformations: None,
+        update_fns: None,
+        max_concurrency: DEFAULT_MAX_CONCURRENCY,
+        pull_request: Default::default(),
+        job: None,
+    }
+}
+
+#[tokio::test]
+async fn create_commit_missing_branch() {
+    let destination_repository = DestinationRepository {
+        owner: "owner".to_string(),
+        name: "repo2".to_string(),
+        git_ref: None,
+        connection: Default::default(),
+        overrides: Default::default(),
+    };
+    let branch = "syncy/owner/repo1";
+
+    let mock_server = MockServer::start().await;
+
+    not_found_mock("GET", "/repos/owner/repo2/git/ref/heads/syncy/owner/repo1")
+        .mount(&mock_server)
+        .await;
+
+    let github_provider = GithubProvider::new(get_config(&destination_repository));
+
+    let instance = github_provider
+        .configure_provider(Some(mock_server.uri()))
+        .unwrap();
+
+    let result = github_provider
+        .create_commit(instance, &destination_repository, "tree_sha", branch)
+        .await;
+
+    assert!(matches!(result, Err(Error::Http(_))));
+
+    mock_server.verify().await;
+}
+
+#[tokio::test]
+async fn create_commit_conflict() {
+    let destination_repository = DestinationRepository {
+        owner: "owner".to_string(),
+        name: "repo2".to_string(),
+        git_ref: None,
+        connection: Default::default(),
+        overrides: Default::default(),
+    };
+    let owner = &destination_repository.owner;
+    let repo = &destination_repository.name;
+    let branch = "syncy/owner/repo1";
+
+    let mock_server = MockServer::start().await;
+
+    get_ref_mock(owner, repo, branch, "parent_sha")
+        .mount(&mock_server)
+        .await;
+    create_commit_mock(
+        owner,
+        repo,
+        json!({
+            "message": "Update from owner/repo1 branch: main",
+            "tree": "tree_sha",
+            "parents": ["parent_sha"],
+        }),
+        "commit_sha",
+    )
+    .mount(&mock_server)
+    .await;
+    update_ref_conflict_mock(owner, repo, branch)
+        .mount(&mock_server)
+        .await;
+
+    let github_provider = GithubProvider::new(get_config(&destination_repository));
+
+    let instance = github_provider
+        .configure_provider(Some(mock_server.uri()))
+        .unwrap();
+
+    let result = github_provider
+        .create_commit(instance, &destination_repository, "tree_sha", branch)
+        .await;
+
+    assert!(matches!(result, Err(Error::Conflict(_))));
+
+    mock_server.verify().await;
+}
+
+#[tokio::test]
+async fn create_commit_preserves_modes() {
+    let destination_repository = DestinationRepository {
+        owner: "owner".to_string(),
+        name: "repo2".to_string(),
+        git_ref: None,
+        connection: Default::default(),
+        overrides: Default::default(),
+    };
+    let owner = &destination_repository.owner;
+    let repo = &destination_repository.name;
+
+    let mock_server = MockServer::start().await;
+
+    get_ref_mock(owner, repo, "main", "base_sha")
+        .mount(&mock_server)
+        .await;
+    get_commit_mock(owner, repo, "base_sha", "base_tree_sha")
+        .mount(&mock_server)
+        .await;
+    create_blob_mock(owner, repo, "#!/bin/sh", "script_blob_sha")
+        .mount(&mock_server)
+        .await;
+    create_blob_mock(owner, repo, "script.sh", "link_blob_sha")
+        .mount(&mock_server)
+        .await;
+    // the submodule points to an existing commit, no blob is created for it
+    create_tree_mock(
+        owner,
+        repo,
+        json!({
+            "base_tree": "base_tree_sha",
+            "tree": [
+                { "path": "link", "mode": "120000", "type": "blob", "sha": "link_blob_sha" },
+                { "path": "script.sh", "mode": "100755", "type": "blob", "sha": "script_blob_sha" },
+                { "path": "vendor/lib", "mode": "160000", "type": "commit", "sha": "submodule_commit_sha" },
+            ],
+        }),
+        "tree_sha",
+    )
+    .mount(&mock_server)
+    .await;
+
+    let events = vec![
+        Event::Create {
+            path: "link".to_string(),
+            content: Some(b"script.sh".to_vec()),
+            kind: NodeKind::Symlink,
+        },
+        Event::Update {
+            path: "script.sh".to_string(),
+            content: Some(b"#!/bin/sh".to_vec()),
+            kind: NodeKind::Executable,
+            sha: "old_sha".to_string(),
+        },
+        Event::Create {
+            path: "vendor/lib".to_string(),
+            content: Some(b"submodule_commit_sha".to_vec()),
+            kind: NodeKind::Gitlink,
+        },
+    ];
+
+    let github_provider = GithubProvider::new(get_config(&destination_repository));
+
+    let instance = github_provider
+        .configure_provider(Some(mock_server.uri()))
+        .unwrap();
+
+    let tree_sha = github_provider
+        .create_commit_tree(instance, &destination_repository, &events, "main")
+        .await
+        .unwrap();
+
+    assert_eq!(tree_sha, "tree_sha");
+    mock_server.verify().await;
+}
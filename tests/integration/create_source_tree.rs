@@ -1,17 +1,31 @@
-use crate::mocks::github::get_content_mock;
+use crate::mocks::github::{
+    get_blob_mock, get_empty_blob_mock, get_repository_commit_mock, get_tree_level_mock,
+    get_tree_mock,
+};
 use serde_json::json;
+use std::time::{Duration, Instant};
 use std::vec;
-use syncy::fixtures::content::get_content_json;
-use syncy::git_tree;
+use syncy::fixtures::content::{
+    get_blob_json, get_tree_entry_json, get_tree_entry_with_mode_json, get_tree_folder_json,
+};
+use syncy::git_tree::{self, NodeKind};
 use syncy::{
-    cli::{DestinationRepository, ParsedConfig, SourceRepository},
+    cli::{
+        Credentials, DestinationRepository, ParsedConfig, SourceRepository, Token,
+        DEFAULT_MAX_CONCURRENCY,
+    },
     github_provider::GithubProvider,
     provider::Provider,
 };
-use wiremock::MockServer;
+use wiremock::matchers::{method, path, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
 
+use regex::Regex;
+use std::collections::HashMap;
+use syncy::cli::parser::{CustomRegex, ReplaceArgs, TemplateArgs};
 use syncy::cli::{MoveArgs, Transformation};
 
+#[allow(clippy::module_inception)]
 mod create_source_tree {
 
     use syncy::fixtures::workdir_path::{create_glob_single, create_workdir_path};
@@ -26,77 +40,89 @@ mod create_source_tree {
                 owner: "owner".to_string(),
                 name: "repo1".to_string(),
                 git_ref: "main".to_string(),
+                connection: Default::default(),
             },
             destinations: vec![DestinationRepository {
                 owner: "owner".to_string(),
                 name: "repo2".to_string(),
+                git_ref: None,
+                connection: Default::default(),
+                overrides: Default::default(),
             }],
-            token: "random_token".to_string(),
+            credentials: Credentials::Token(Token::from("random_token")),
+            api_url: None,
+            web_url: None,
             destination_files: create_workdir_path(""),
             origin_files: create_workdir_path(""),
             transformations: None,
-            update_fns: None
+            update_fns: None,
+            max_concurrency: DEFAULT_MAX_CONCURRENCY,
+            pull_request: Default::default(),
+            job: None,
         };
 
         let mock_server = MockServer::start().await;
 
-        let content1 = get_content_json("test1", "test1", Some("my_content"), "file");
-        let content2 = get_content_json("test2", "test2", Some("my_content"), "file");
-        let content_item_response = json!([&content1, &content2]);
-
-        get_content_mock(
+        get_tree_mock(
             &config.source.owner,
             &config.source.name,
-            None,
-            content_item_response,
-            None,
+            "main",
+            vec![
+                get_tree_entry_json("test1", "sha1"),
+                get_tree_entry_json("test2", "sha2"),
+            ],
         )
         .mount(&mock_server)
         .await;
 
-        get_content_mock(
+        get_blob_mock(
             &config.source.owner,
             &config.source.name,
-            Some("test1"),
-            json!(content1),
-            None,
+            "sha1",
+            "my_content",
         )
         .mount(&mock_server)
         .await;
 
-        get_content_mock(
+        get_blob_mock(
             &config.source.owner,
             &config.source.name,
-            Some("test2"),
-            json!(content2),
-            None,
+            "sha2",
+            "my_content",
         )
         .mount(&mock_server)
         .await;
 
-        let github_provider = GithubProvider { config };
+        let github_provider = GithubProvider::new(config);
 
-        let instance = github_provider.configure_provider(Some(mock_server.uri()));
+        let instance = github_provider
+            .configure_provider(Some(mock_server.uri()))
+            .unwrap();
 
-        let source_tree = github_provider.create_source_tree(instance.clone()).await;
+        let source_tree = github_provider
+            .create_source_tree(instance.clone())
+            .await
+            .unwrap();
 
         let expected_tree = git_tree::Tree::from([
             (
                 "test1".to_string(),
                 git_tree::Node {
-                    path: content1.path.to_string(),
-                    content: content1.decoded_content(),
-                    git_url: content1.git_url,
-                    sha: content1.sha,
+                    path: "test1".to_string(),
+                    content: Some(b"my_content".to_vec()),
+                    kind: NodeKind::Blob,
+                    git_url: "https://example.net/git/blobs/sha1".to_string(),
+                    sha: "sha1".to_string(),
                 },
             ),
             (
                 "test2".to_string(),
                 git_tree::Node {
-                    path: content2.path.to_string(),
-                    content: content2.decoded_content(),
-                    git_url: content2.git_url,
-                    sha: content2.sha,
+                    path: "test2".to_string(),
+                    content: Some(b"my_content".to_vec()),
+                    kind: NodeKind::Blob,
+                    git_url: "https://example.net/git/blobs/sha2".to_string(),
+                    sha: "sha2".to_string(),
                 },
             ),
         ]);
@@ -113,12 +139,18 @@ mod create_source_tree {
                 owner: "owner".to_string(),
                 name: "repo1".to_string(),
                 git_ref: "main".to_string(),
+                connection: Default::default(),
             },
             destinations: vec![DestinationRepository {
                 owner: "owner".to_string(),
                 name: "repo2".to_string(),
+                git_ref: None,
+                connection: Default::default(),
+                overrides: Default::default(),
             }],
-            token: "random_token".to_string(),
+            credentials: Credentials::Token(Token::from("random_token")),
+            api_url: None,
+            web_url: None,
             destination_files: create_glob_single("folder/**"),
             origin_files: create_glob_single("folder/**"),
             transformations: Some(vec![Transformation::Move {
@@ -126,93 +158,391 @@ mod create_source_tree {
                     before: "".to_string(),
                     after: "repo_one_folder".to_string(),
                 },
+                paths: None,
             }]),
-            update_fns: None
+            update_fns: None,
+            max_concurrency: DEFAULT_MAX_CONCURRENCY,
+            pull_request: Default::default(),
+            job: None,
         };
 
         let mock_server = MockServer::start().await;
 
-        let source_file_root = get_content_json("test1", "test1", Some("my_content"), "file");
-        let source_content_folder = get_content_json("folder", "folder", None, "dir");
-        let source_content_file =
-            get_content_json("folder/test2", "test2", Some("my_content_2"), "file");
-        let source_content_file_second =
-            get_content_json("folder/test3", "test3", Some("my_content_3"), "file");
+        // only the `folder` subtree is listed recursively, `test1` is never downloaded
+        get_tree_level_mock(
+            &config.source.owner,
+            &config.source.name,
+            "main",
+            vec![
+                get_tree_entry_json("test1", "sha1"),
+                get_tree_folder_json("folder", "folder_sha"),
+            ],
+        )
+        .mount(&mock_server)
+        .await;
 
-        let content_item_response_first = json!([&source_file_root, &source_content_folder]);
+        get_tree_mock(
+            &config.source.owner,
+            &config.source.name,
+            "folder_sha",
+            vec![
+                get_tree_entry_json("test2", "sha2"),
+                get_tree_entry_json("test3", "sha3"),
+            ],
+        )
+        .mount(&mock_server)
+        .await;
 
-        get_content_mock(
+        get_blob_mock(
             &config.source.owner,
             &config.source.name,
-            None,
-            content_item_response_first,
-            None,
+            "sha2",
+            "my_content_2",
         )
         .mount(&mock_server)
         .await;
 
-        get_content_mock(
+        get_blob_mock(
+            &config.source.owner,
+            &config.source.name,
+            "sha3",
+            "my_content_3",
+        )
+        .mount(&mock_server)
+        .await;
+
+        let github_provider = GithubProvider::new(config);
+
+        let instance = github_provider
+            .configure_provider(Some(mock_server.uri()))
+            .unwrap();
+
+        let source_tree = github_provider
+            .create_source_tree(instance.clone())
+            .await
+            .unwrap();
+
+        let expected_tree = git_tree::Tree::from([
+            (
+                "repo_one_folder/folder/test2".to_string(),
+                git_tree::Node {
+                    path: "repo_one_folder/folder/test2".to_string(),
+                    content: Some(b"my_content_2".to_vec()),
+                    kind: NodeKind::Blob,
+                    git_url: "https://example.net/git/blobs/sha2".to_string(),
+                    sha: "sha2".to_string(),
+                },
+            ),
+            (
+                "repo_one_folder/folder/test3".to_string(),
+                git_tree::Node {
+                    path: "repo_one_folder/folder/test3".to_string(),
+                    content: Some(b"my_content_3".to_vec()),
+                    kind: NodeKind::Blob,
+                    git_url: "https://example.net/git/blobs/sha3".to_string(),
+                    sha: "sha3".to_string(),
+                },
+            ),
+        ]);
+        assert_eq!(source_tree, expected_tree);
+
+        mock_server.verify().await;
+    }
+
+    #[tokio::test]
+    async fn binary_content() {
+        let config = ParsedConfig {
+            version: "0.1".to_string(),
+            source: SourceRepository {
+                owner: "owner".to_string(),
+                name: "repo1".to_string(),
+                git_ref: "main".to_string(),
+                connection: Default::default(),
+            },
+            destinations: vec![DestinationRepository {
+                owner: "owner".to_string(),
+                name: "repo2".to_string(),
+                git_ref: None,
+                connection: Default::default(),
+                overrides: Default::default(),
+            }],
+            credentials: Credentials::Token(Token::from("random_token")),
+            api_url: None,
+            web_url: None,
+            destination_files: create_workdir_path(""),
+            origin_files: create_workdir_path(""),
+            transformations: Some(vec![
+                Transformation::Move {
+                    args: MoveArgs {
+                        before: "".to_string(),
+                        after: "assets".to_string(),
+                    },
+                    paths: None,
+                },
+                Transformation::Replace {
+                    args: ReplaceArgs {
+                        before: CustomRegex(Regex::new("PNG").unwrap()),
+                        after: "JPG".to_string(),
+                    },
+                    paths: None,
+                },
+            ]),
+            update_fns: None,
+            max_concurrency: DEFAULT_MAX_CONCURRENCY,
+            pull_request: Default::default(),
+            job: None,
+        };
+        let image = b"\x89PNG\r\n\x1a\n\x00\x00\xff".to_vec();
+
+        let mock_server = MockServer::start().await;
+
+        get_tree_mock(
+            &config.source.owner,
+            &config.source.name,
+            "main",
+            vec![
+                get_tree_entry_json("image.png", "sha1"),
+                get_tree_entry_json("readme", "sha2"),
+            ],
+        )
+        .mount(&mock_server)
+        .await;
+
+        get_blob_mock(&config.source.owner, &config.source.name, "sha1", &image)
+            .mount(&mock_server)
+            .await;
+
+        get_blob_mock(
+            &config.source.owner,
+            &config.source.name,
+            "sha2",
+            "a PNG image",
+        )
+        .mount(&mock_server)
+        .await;
+
+        let github_provider = GithubProvider::new(config);
+
+        let instance = github_provider
+            .configure_provider(Some(mock_server.uri()))
+            .unwrap();
+
+        let source_tree = github_provider
+            .create_source_tree(instance.clone())
+            .await
+            .unwrap();
+
+        // the replace is applied to text files only, binary content is kept intact
+        let expected_tree = git_tree::Tree::from([
+            (
+                "assets/image.png".to_string(),
+                git_tree::Node {
+                    path: "assets/image.png".to_string(),
+                    content: Some(image),
+                    kind: NodeKind::Blob,
+                    git_url: "https://example.net/git/blobs/sha1".to_string(),
+                    sha: "sha1".to_string(),
+                },
+            ),
+            (
+                "assets/readme".to_string(),
+                git_tree::Node {
+                    path: "assets/readme".to_string(),
+                    content: Some(b"a JPG image".to_vec()),
+                    kind: NodeKind::Blob,
+                    git_url: "https://example.net/git/blobs/sha2".to_string(),
+                    sha: "sha2".to_string(),
+                },
+            ),
+        ]);
+        assert_eq!(source_tree, expected_tree);
+
+        mock_server.verify().await;
+    }
+
+    fn get_config() -> ParsedConfig {
+        ParsedConfig {
+            version: "0.1".to_string(),
+            source: SourceRepository {
+                owner: "owner".to_string(),
+                name: "repo1".to_string(),
+                git_ref: "main".to_string(),
+                connection: Default::default(),
+            },
+            destinations: vec![DestinationRepository {
+                owner: "owner".to_string(),
+                name: "repo2".to_string(),
+                git_ref: None,
+                connection: Default::default(),
+                overrides: Default::default(),
+            }],
+            credentials: Credentials::Token(Token::from("random_token")),
+            api_url: None,
+            web_url: None,
+            destination_files: create_workdir_path(""),
+            origin_files: create_workdir_path(""),
+            transformations: None,
+            update_fns: None,
+            max_concurrency: DEFAULT_MAX_CONCURRENCY,
+            pull_request: Default::default(),
+            job: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn large_file() {
+        let config = get_config();
+        let content = "0123456789abcdef".repeat(128 * 1024);
+
+        let mock_server = MockServer::start().await;
+
+        get_tree_mock(
+            &config.source.owner,
+            &config.source.name,
+            "main",
+            vec![get_tree_entry_json("large", "sha1")],
+        )
+        .mount(&mock_server)
+        .await;
+
+        get_blob_mock(&config.source.owner, &config.source.name, "sha1", &content)
+            .mount(&mock_server)
+            .await;
+
+        let github_provider = GithubProvider::new(config);
+
+        let instance = github_provider
+            .configure_provider(Some(mock_server.uri()))
+            .unwrap();
+
+        let source_tree = github_provider
+            .create_source_tree(instance.clone())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            source_tree["large"].content.as_deref(),
+            Some(content.as_bytes())
+        );
+
+        mock_server.verify().await;
+    }
+
+    #[tokio::test]
+    async fn missing_blob_content() {
+        let config = get_config();
+
+        let mock_server = MockServer::start().await;
+
+        get_tree_mock(
             &config.source.owner,
             &config.source.name,
-            Some("test1"),
-            json!(source_file_root),
-            None,
+            "main",
+            vec![get_tree_entry_json("large", "sha1")],
         )
         .mount(&mock_server)
         .await;
 
-        get_content_mock(
+        get_empty_blob_mock(&config.source.owner, &config.source.name, "sha1", 2_000_000)
+            .mount(&mock_server)
+            .await;
+
+        let github_provider = GithubProvider::new(config);
+
+        let instance = github_provider
+            .configure_provider(Some(mock_server.uri()))
+            .unwrap();
+
+        let error = github_provider
+            .create_source_tree(instance.clone())
+            .await
+            .unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "unexpected provider response: can't read large from owner/repo1: expected 2000000 bytes, got 0"
+        );
+
+        mock_server.verify().await;
+    }
+
+    #[tokio::test]
+    async fn modes() {
+        let config = get_config();
+
+        let mock_server = MockServer::start().await;
+
+        get_tree_mock(
             &config.source.owner,
             &config.source.name,
-            Some("folder"),
-            json!([source_content_file, source_content_file_second]),
-            None,
+            "main",
+            vec![
+                get_tree_entry_with_mode_json("script.sh", "sha1", "100755"),
+                get_tree_entry_with_mode_json("link", "sha2", "120000"),
+                get_tree_entry_with_mode_json("vendor/lib", "submodule_sha", "160000"),
+            ],
         )
         .mount(&mock_server)
         .await;
 
-        get_content_mock(
+        get_blob_mock(
             &config.source.owner,
             &config.source.name,
-            Some("folder/test2"),
-            json!(source_content_file),
-            None,
+            "sha1",
+            "#!/bin/sh",
         )
         .mount(&mock_server)
         .await;
-        get_content_mock(
+
+        get_blob_mock(
             &config.source.owner,
             &config.source.name,
-            Some("folder/test3"),
-            json!(source_content_file_second),
-            None,
+            "sha2",
+            "script.sh",
         )
         .mount(&mock_server)
         .await;
 
-        let github_provider = GithubProvider { config };
+        let github_provider = GithubProvider::new(config);
 
-        let instance = github_provider.configure_provider(Some(mock_server.uri()));
+        let instance = github_provider
+            .configure_provider(Some(mock_server.uri()))
+            .unwrap();
 
-        let source_tree = github_provider.create_source_tree(instance.clone()).await;
+        let source_tree = github_provider
+            .create_source_tree(instance.clone())
+            .await
+            .unwrap();
 
         let expected_tree = git_tree::Tree::from([
             (
-                "repo_one_folder/folder/test2".to_string(),
+                "script.sh".to_string(),
                 git_tree::Node {
-                    path: "repo_one_folder/folder/test2".to_string(),
-                    content: source_content_file.decoded_content(),
-                    git_url: source_content_file.git_url,
-                    sha: source_content_file.sha,
+                    path: "script.sh".to_string(),
+                    content: Some(b"#!/bin/sh".to_vec()),
+                    kind: NodeKind::Executable,
+                    git_url: "https://example.net/git/blobs/sha1".to_string(),
+                    sha: "sha1".to_string(),
                 },
             ),
             (
-                "repo_one_folder/folder/test3".to_string(),
+                "link".to_string(),
                 git_tree::Node {
-                    path: "repo_one_folder/folder/test3".to_string(),
-                    content: source_content_file_second.decoded_content(),
-                    git_url: source_content_file_second.git_url,
-                    sha: source_content_file_second.sha,
+                    path: "link".to_string(),
+                    content: Some(b"script.sh".to_vec()),
+                    kind: NodeKind::Symlink,
+                    git_url: "https://example.net/git/blobs/sha2".to_string(),
+                    sha: "sha2".to_string(),
+                },
+            ),
+            (
+                "vendor/lib".to_string(),
+                git_tree::Node {
+                    path: "vendor/lib".to_string(),
+                    content: Some(b"submodule_sha".to_vec()),
+                    kind: NodeKind::Gitlink,
+                    git_url: "https://example.net/git/blobs/submodule_sha".to_string(),
+                    sha: "submodule_sha".to_string(),
                 },
             ),
         ]);
@@ -220,4 +550,244 @@ mod create_source_tree {
 
         mock_server.verify().await;
     }
+
+    #[tokio::test]
+    async fn missing_root_path() {
+        let config = ParsedConfig {
+            origin_files: create_glob_single("docs/api/**"),
+            ..get_config()
+        };
+
+        let mock_server = MockServer::start().await;
+
+        get_tree_level_mock(
+            &config.source.owner,
+            &config.source.name,
+            "main",
+            vec![get_tree_folder_json("docs", "docs_sha")],
+        )
+        .mount(&mock_server)
+        .await;
+
+        get_tree_level_mock(
+            &config.source.owner,
+            &config.source.name,
+            "docs_sha",
+            vec![get_tree_entry_json("index.md", "sha1")],
+        )
+        .mount(&mock_server)
+        .await;
+
+        let github_provider = GithubProvider::new(config);
+
+        let instance = github_provider
+            .configure_provider(Some(mock_server.uri()))
+            .unwrap();
+
+        let source_tree = github_provider
+            .create_source_tree(instance.clone())
+            .await
+            .unwrap();
+
+        assert_eq!(source_tree, git_tree::Tree::new());
+
+        mock_server.verify().await;
+    }
+
+    #[tokio::test]
+    async fn template_with_source_sha() {
+        let config = ParsedConfig {
+            transformations: Some(vec![
+                Transformation::Move {
+                    args: MoveArgs {
+                        before: "".to_string(),
+                        after: "vendor".to_string(),
+                    },
+                    paths: None,
+                },
+                Transformation::Template {
+                    args: TemplateArgs {
+                        variables: HashMap::new(),
+                    },
+                    paths: None,
+                },
+            ]),
+            ..get_config()
+        };
+
+        let mock_server = MockServer::start().await;
+
+        get_tree_mock(
+            &config.source.owner,
+            &config.source.name,
+            "main",
+            vec![get_tree_entry_json("version", "sha1")],
+        )
+        .mount(&mock_server)
+        .await;
+
+        get_blob_mock(
+            &config.source.owner,
+            &config.source.name,
+            "sha1",
+            "${source.owner}/${source.name}@${source.ref} (${source.sha})",
+        )
+        .mount(&mock_server)
+        .await;
+
+        get_repository_commit_mock(
+            &config.source.owner,
+            &config.source.name,
+            "main",
+            "commit_sha",
+        )
+        .mount(&mock_server)
+        .await;
+
+        let github_provider = GithubProvider::new(config);
+
+        let instance = github_provider
+            .configure_provider(Some(mock_server.uri()))
+            .unwrap();
+
+        let source_tree = github_provider
+            .create_source_tree(instance.clone())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            source_tree["vendor/version"].content.as_deref(),
+            Some(&b"owner/repo1@main (commit_sha)"[..])
+        );
+
+        mock_server.verify().await;
+    }
+
+    #[tokio::test]
+    async fn max_concurrency_caps_requests_across_trees() {
+        let config = ParsedConfig {
+            destinations: vec![],
+            max_concurrency: 1,
+            ..get_config()
+        };
+        let delay = Duration::from_millis(100);
+
+        let mock_server = MockServer::start().await;
+
+        get_tree_mock(
+            &config.source.owner,
+            &config.source.name,
+            "main",
+            vec![
+                get_tree_entry_json("test1", "sha1"),
+                get_tree_entry_json("test2", "sha2"),
+            ],
+        )
+        .expect(2)
+        .mount(&mock_server)
+        .await;
+
+        for sha in ["sha1", "sha2"] {
+            Mock::given(method("GET"))
+                .and(path(format!("/repos/owner/repo1/git/blobs/{}", sha)))
+                .respond_with(
+                    ResponseTemplate::new(200)
+                        .set_body_json(get_blob_json(sha, "my_content"))
+                        .set_delay(delay),
+                )
+                .expect(2)
+                .mount(&mock_server)
+                .await;
+        }
+
+        let github_provider = GithubProvider::new(config);
+        let instance = github_provider
+            .configure_provider(Some(mock_server.uri()))
+            .unwrap();
+
+        // two trees fetched at once, as for two destinations, share the limit:
+        // with a single permit the four delayed blobs are fetched one by one
+        let started = Instant::now();
+        let (first, second) = futures::join!(
+            github_provider.fetch_source_tree(instance.clone()),
+            github_provider.fetch_source_tree(instance.clone()),
+        );
+        first.unwrap();
+        second.unwrap();
+
+        assert!(started.elapsed() >= delay * 4);
+
+        mock_server.verify().await;
+    }
+
+    #[tokio::test]
+    async fn truncated_tree() {
+        let config = get_config();
+
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path("/repos/owner/repo1/git/trees/main"))
+            .and(query_param("recursive", "1"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "sha": "root_sha",
+                "tree": [get_tree_entry_json("test1", "sha1")],
+                "truncated": true,
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        get_tree_level_mock(
+            &config.source.owner,
+            &config.source.name,
+            "root_sha",
+            vec![
+                get_tree_entry_json("test1", "sha1"),
+                get_tree_folder_json("folder", "folder_sha"),
+            ],
+        )
+        .mount(&mock_server)
+        .await;
+        get_tree_level_mock(
+            &config.source.owner,
+            &config.source.name,
+            "folder_sha",
+            vec![get_tree_entry_json("test2", "sha2")],
+        )
+        .mount(&mock_server)
+        .await;
+        get_blob_mock(
+            &config.source.owner,
+            &config.source.name,
+            "sha1",
+            "content1",
+        )
+        .mount(&mock_server)
+        .await;
+        get_blob_mock(
+            &config.source.owner,
+            &config.source.name,
+            "sha2",
+            "content2",
+        )
+        .mount(&mock_server)
+        .await;
+
+        let github_provider = GithubProvider::new(config);
+        let instance = github_provider
+            .configure_provider(Some(mock_server.uri()))
+            .unwrap();
+
+        let source_tree = github_provider.create_source_tree(instance).await.unwrap();
+
+        let mut paths: Vec<&String> = source_tree.keys().collect();
+        paths.sort();
+        assert_eq!(paths, vec!["folder/test2", "test1"]);
+        assert_eq!(
+            source_tree["folder/test2"].content,
+            Some(b"content2".to_vec())
+        );
+
+        mock_server.verify().await;
+    }
 }
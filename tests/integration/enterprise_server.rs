@@ -0,0 +1,193 @@
+use serde_json::json;
+use std::path::PathBuf;
+use syncy::fixtures::workdir_path::create_workdir_path;
+use syncy::{
+    cli::{
+        Connection, Credentials, DestinationRepository, GithubApp, ParsedConfig, SourceRepository,
+        Token, DEFAULT_MAX_CONCURRENCY,
+    },
+    github_clients::GithubClients,
+    github_provider::GithubProvider,
+    provider::Provider,
+};
+use wiremock::matchers::{header, method, path, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+/// A GitHub Enterprise Server serves its REST API below `/api/v3`.
+fn api_v3(route: &str) -> String {
+    format!("/api/v3/{}", route)
+}
+
+fn get_ref_json(branch: &str, sha: &str) -> serde_json::Value {
+    json!({
+        "ref": format!("refs/heads/{}", branch),
+        "node_id": "",
+        "url": "https://example.net",
+        "object": {
+            "type": "commit",
+            "sha": sha,
+            "url": "https://example.net",
+        },
+    })
+}
+
+fn get_provider(mock_server: &MockServer) -> GithubProvider {
+    let config = ParsedConfig {
+        version: "0.1".to_string(),
+        source: SourceRepository {
+            owner: "owner".to_string(),
+            name: "repo1".to_string(),
+            git_ref: "main".to_string(),
+            connection: Default::default(),
+        },
+        destinations: vec![get_destination()],
+        credentials: Credentials::Token(Token::from("random_token")),
+        // no trailing slash, as in `api_url: https://github.example.com/api/v3`
+        api_url: Some(format!("{}/api/v3", mock_server.uri())),
+        web_url: None,
+        destination_files: create_workdir_path(""),
+        origin_files: create_workdir_path(""),
+        transformations: None,
+        update_fns: None,
+        max_concurrency: DEFAULT_MAX_CONCURRENCY,
+        pull_request: Default::default(),
+        job: None,
+    };
+
+    GithubProvider::new(config)
+}
+
+fn get_destination() -> DestinationRepository {
+    DestinationRepository {
+        owner: "owner".to_string(),
+        name: "repo2".to_string(),
+        git_ref: None,
+        connection: Default::default(),
+        overrides: Default::default(),
+    }
+}
+
+#[tokio::test]
+async fn requests_keep_the_api_path() {
+    let destination = get_destination();
+    let branch = "syncy/owner/repo1/main";
+
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path(api_v3("repos/owner/repo2")))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "default_branch": "main",
+        })))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path(api_v3("repos/owner/repo2/git/ref/heads/main")))
+        .respond_with(ResponseTemplate::new(200).set_body_json(get_ref_json("main", "base_sha")))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path(api_v3(&format!(
+            "repos/owner/repo2/git/matching-refs/heads/{}",
+            branch
+        ))))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path(api_v3("repos/owner/repo2/git/refs")))
+        .respond_with(ResponseTemplate::new(201).set_body_json(get_ref_json(branch, "base_sha")))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path(api_v3("repos/owner/repo2/pulls")))
+        .and(query_param("state", "open"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path(api_v3("repos/owner/repo2/pulls")))
+        .respond_with(ResponseTemplate::new(201).set_body_json(json!({ "number": 1 })))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let github_provider = get_provider(&mock_server);
+    let instance = github_provider.configure_provider(None).unwrap();
+
+    let base_branch = github_provider
+        .get_destination_base_branch(instance.clone(), &destination)
+        .await
+        .unwrap();
+    github_provider
+        .create_destination_branch(
+            instance.clone(),
+            &destination,
+            &base_branch,
+            branch,
+            "tree_sha",
+        )
+        .await
+        .unwrap();
+    github_provider
+        .create_pull_request_destination(instance, &destination, &base_branch, branch)
+        .await
+        .unwrap();
+
+    mock_server.verify().await;
+}
+
+#[tokio::test]
+async fn app_installation_tokens_keep_the_api_path() {
+    let mock_server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path(api_v3("repos/owner/repo2/installation")))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "id": 1 })))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path(api_v3("app/installations/1/access_tokens")))
+        .respond_with(ResponseTemplate::new(201).set_body_json(json!({
+            "token": "ghs_owner",
+            "expires_at": "2999-01-01T00:00:00Z",
+        })))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path(api_v3("repos/owner/repo2")))
+        .and(header("Authorization", "Bearer ghs_owner"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "default_branch": "main",
+        })))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let connection = Connection {
+        api_url: Some(format!("{}/api/v3/", mock_server.uri())),
+        web_url: mock_server.uri(),
+        credentials: Credentials::App(GithubApp {
+            id: 42,
+            private_key_path: PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+                .join("tests/integration/fixtures/app_private_key.pem"),
+        }),
+    };
+    let clients = GithubClients::default();
+    let instance = clients.get(&connection, "owner", "repo2").await.unwrap();
+
+    let base_branch = get_provider(&mock_server)
+        .get_destination_base_branch(instance, &get_destination())
+        .await
+        .unwrap();
+
+    assert_eq!(base_branch, "main");
+    mock_server.verify().await;
+}
@@ -1,232 +1,158 @@
-use crate::mocks::github::get_content_mock;
-use serde_json::json;
+use crate::mocks::github::{get_blob_mock, get_tree_level_mock, get_tree_mock};
 use syncy::{
-    cli::{DestinationRepository, ParsedConfig, SourceRepository},
+    cli::{
+        Credentials, DestinationRepository, ParsedConfig, SourceRepository, Token,
+        DEFAULT_MAX_CONCURRENCY,
+    },
     github_provider::GithubProvider,
     provider::Provider,
 };
 use syncy::{
     event::Event,
     fixtures::{
-        content::get_content_json,
+        content::{get_tree_entry_json, get_tree_folder_json},
         workdir_path::{create_glob_single, create_workdir_path},
     },
 };
 use wiremock::MockServer;
 
 use syncy::cli::{MoveArgs, Transformation};
-use syncy::git_tree::GitTree;
+use syncy::git_tree::{GitTree, NodeKind};
 
-#[tokio::test]
-async fn generate_events_success() {
-    let destination_repository = DestinationRepository {
-        owner: "owner".to_string(),
-        name: "repo2".to_string(),
-    };
-    let config = ParsedConfig {
-        version: "0.1".to_string(),
-        source: SourceRepository {
-            owner: "owner".to_string(),
-            name: "repo1".to_string(),
-            git_ref: "main".to_string(),
-        },
-        destinations: vec![destination_repository.clone()],
-        token: "random_token".to_string(),
-        destination_files: create_glob_single("repo_one_folder/**"),
-        origin_files: create_glob_single("folder/**"),
-        transformations: Some(vec![Transformation::Move {
-            args: MoveArgs {
-                before: "".to_string(),
-                after: "repo_one_folder".to_string(),
-            },
-        }]),
-        update_fns: None
-    };
-
-    let mock_server = MockServer::start().await;
-
-    let source_file_root = get_content_json("test1", "test1", Some("source_my_content"), "file");
-    let source_content_folder = get_content_json("folder", "folder", None, "dir");
-    let source_content_file =
-        get_content_json("folder/test2", "test2", Some("source_my_content_2"), "file");
-    let source_content_file_second =
-        get_content_json("folder/test3", "test3", Some("source_my_content_3"), "file");
-
-    let content_item_response_first = json!([&source_file_root, &source_content_folder]);
-
-    get_content_mock(
+async fn mount_source_mocks(config: &ParsedConfig, mock_server: &MockServer) {
+    get_tree_level_mock(
         &config.source.owner,
         &config.source.name,
-        None,
-        content_item_response_first,
-        None,
+        "main",
+        vec![
+            get_tree_entry_json("test1", "source_sha1"),
+            get_tree_folder_json("folder", "source_folder_sha"),
+        ],
     )
-    .mount(&mock_server)
+    .mount(mock_server)
     .await;
 
-    get_content_mock(
+    get_tree_mock(
         &config.source.owner,
         &config.source.name,
-        Some("test1"),
-        json!(source_file_root),
-        None,
+        "source_folder_sha",
+        vec![
+            get_tree_entry_json("test2", "source_sha2"),
+            get_tree_entry_json("test3", "source_sha3"),
+        ],
     )
-    .mount(&mock_server)
+    .mount(mock_server)
     .await;
 
-    get_content_mock(
+    get_blob_mock(
         &config.source.owner,
         &config.source.name,
-        Some("folder"),
-        json!([source_content_file, source_content_file_second]),
-        None,
+        "source_sha2",
+        "source_my_content_2",
     )
-    .mount(&mock_server)
+    .mount(mock_server)
     .await;
 
-    get_content_mock(
-        &config.source.owner,
-        &config.source.name,
-        Some("folder/test2"),
-        json!(source_content_file),
-        None,
-    )
-    .mount(&mock_server)
-    .await;
-    get_content_mock(
+    get_blob_mock(
         &config.source.owner,
         &config.source.name,
-        Some("folder/test3"),
-        json!(source_content_file_second),
-        None,
+        "source_sha3",
+        "source_my_content_3",
     )
-    .mount(&mock_server)
+    .mount(mock_server)
     .await;
+}
 
-    let destination_root_folder =
-        get_content_json("repo_one_folder", "repo_one_folder", None, "dir");
-    let destination_file_root = get_content_json(
-        "repo_one_folder/test1",
-        "test1",
-        Some("dest_my_content"),
-        "file",
-    );
-    let destination_content_folder =
-        get_content_json("repo_one_folder/folder", "folder", None, "dir");
-    let destination_content_file = get_content_json(
-        "repo_one_folder/folder/test2",
-        "test2",
-        Some("dest_my_content_2"),
-        "file",
-    );
-    let destination_content_file_second = get_content_json(
-        "repo_one_folder/folder/test4",
-        "test4",
-        Some("dest_my_content_3"),
-        "file",
-    );
-
-    get_content_mock(
+async fn mount_destination_mocks(
+    destination_repository: &DestinationRepository,
+    mock_server: &MockServer,
+) {
+    get_tree_level_mock(
         &destination_repository.owner,
         &destination_repository.name,
-        None,
-        json!(&destination_root_folder),
-        Some("main"),
+        "main",
+        vec![
+            get_tree_entry_json("readme", "dest_readme_sha"),
+            get_tree_folder_json("repo_one_folder", "dest_root_folder_sha"),
+        ],
     )
-    .mount(&mock_server)
+    .mount(mock_server)
     .await;
 
-    get_content_mock(
+    get_tree_mock(
         &destination_repository.owner,
         &destination_repository.name,
-        Some("repo_one_folder"),
-        json!([&destination_file_root, &destination_content_folder]),
-        Some("main"),
+        "dest_root_folder_sha",
+        vec![
+            get_tree_entry_json("test1", "dest_sha1"),
+            get_tree_folder_json("folder", "dest_folder_sha"),
+            get_tree_entry_json("folder/test2", "dest_sha2"),
+            get_tree_entry_json("folder/test4", "dest_sha4"),
+        ],
     )
-    .mount(&mock_server)
+    .mount(mock_server)
     .await;
 
-    get_content_mock(
+    get_blob_mock(
         &destination_repository.owner,
         &destination_repository.name,
-        Some("repo_one_folder/test1"),
-        json!([&destination_file_root]),
-        Some("main"),
+        "dest_sha1",
+        "dest_my_content",
     )
-    .mount(&mock_server)
+    .mount(mock_server)
     .await;
 
-    get_content_mock(
+    get_blob_mock(
         &destination_repository.owner,
         &destination_repository.name,
-        Some("repo_one_folder/folder"),
-        json!([&destination_content_file, &destination_content_file_second]),
-        Some("main"),
+        "dest_sha2",
+        "dest_my_content_2",
     )
-    .mount(&mock_server)
+    .mount(mock_server)
     .await;
 
-    get_content_mock(
-        &destination_repository.owner,
-        &destination_repository.name,
-        Some("repo_one_folder/folder/test2"),
-        json!([&destination_content_file]),
-        Some("main"),
-    )
-    .mount(&mock_server)
-    .await;
-    get_content_mock(
+    get_blob_mock(
         &destination_repository.owner,
         &destination_repository.name,
-        Some("repo_one_folder/folder/test4"),
-        json!([&destination_content_file_second]),
-        Some("main"),
+        "dest_sha4",
+        "dest_my_content_3",
     )
-    .mount(&mock_server)
+    .mount(mock_server)
     .await;
+}
 
-    let github_provider = GithubProvider { config };
-
-    let instance = github_provider.configure_provider(Some(mock_server.uri()));
-
-    let source_tree = github_provider.create_source_tree(instance.clone()).await;
-
-    let dest_tree = github_provider
-        .create_destination_tree(instance.clone(), &destination_repository)
-        .await;
-
-    let mut events = source_tree.generate_events(&dest_tree);
-    events.sort();
-
-    let expected_events = vec![
+fn get_expected_events() -> Vec<Event> {
+    vec![
         Event::Create {
             path: "repo_one_folder/folder/test3".to_string(),
-            content: source_content_file_second.decoded_content(),
+            content: Some(b"source_my_content_3".to_vec()),
+            kind: NodeKind::Blob,
         },
         Event::Update {
             path: "repo_one_folder/folder/test2".to_string(),
-            content: source_content_file.decoded_content(),
-            sha: destination_content_file.sha,
+            content: Some(b"source_my_content_2".to_vec()),
+            kind: NodeKind::Blob,
+            sha: "dest_sha2".to_string(),
         },
         Event::Delete {
             path: "repo_one_folder/folder/test4".to_string(),
-            sha: destination_content_file_second.sha,
+            sha: "dest_sha4".to_string(),
         },
         Event::Delete {
             path: "repo_one_folder/test1".to_string(),
-            sha: destination_file_root.sha,
+            sha: "dest_sha1".to_string(),
         },
-    ];
-    assert_eq!(events, expected_events);
-
-    mock_server.verify().await;
+    ]
 }
 
 #[tokio::test]
-async fn generate_events_success_with_config_path() {
+async fn generate_events_success() {
     let destination_repository = DestinationRepository {
         owner: "owner".to_string(),
         name: "repo2".to_string(),
+        git_ref: Some("main".to_string()),
+        connection: Default::default(),
+        overrides: Default::default(),
     };
     let config = ParsedConfig {
         version: "0.1".to_string(),
@@ -234,162 +160,117 @@ async fn generate_events_success_with_config_path() {
             owner: "owner".to_string(),
             name: "repo1".to_string(),
             git_ref: "main".to_string(),
+            connection: Default::default(),
         },
         destinations: vec![destination_repository.clone()],
-        token: "random_token".to_string(),
-        origin_files: create_workdir_path("folder"),
-        destination_files: create_workdir_path("repo_one_folder"),
+        credentials: Credentials::Token(Token::from("random_token")),
+        api_url: None,
+        web_url: None,
+        destination_files: create_glob_single("repo_one_folder/**"),
+        origin_files: create_glob_single("folder/**"),
         transformations: Some(vec![Transformation::Move {
             args: MoveArgs {
                 before: "".to_string(),
                 after: "repo_one_folder".to_string(),
             },
+            paths: None,
         }]),
-        update_fns: None
+        update_fns: None,
+        max_concurrency: DEFAULT_MAX_CONCURRENCY,
+        pull_request: Default::default(),
+        job: None,
     };
 
     let mock_server = MockServer::start().await;
 
-    let source_content_file =
-        get_content_json("folder/test2", "test2", Some("source_my_content_2"), "file");
-    let source_content_file_second =
-        get_content_json("folder/test3", "test3", Some("source_my_content_3"), "file");
+    mount_source_mocks(&config, &mock_server).await;
+    mount_destination_mocks(&destination_repository, &mock_server).await;
 
-    let content_item_response_first = json!([&source_content_file, &source_content_file_second]);
+    let github_provider = GithubProvider::new(config);
 
-    get_content_mock(
-        &config.source.owner,
-        &config.source.name,
-        Some("folder"),
-        content_item_response_first,
-        None,
-    )
-    .mount(&mock_server)
-    .await;
+    let instance = github_provider
+        .configure_provider(Some(mock_server.uri()))
+        .unwrap();
 
-    get_content_mock(
-        &config.source.owner,
-        &config.source.name,
-        Some("folder/test2"),
-        json!(source_content_file),
-        None,
-    )
-    .mount(&mock_server)
-    .await;
+    let source_tree = github_provider
+        .create_source_tree(instance.clone())
+        .await
+        .unwrap();
 
-    get_content_mock(
-        &config.source.owner,
-        &config.source.name,
-        Some("folder/test3"),
-        json!([source_content_file_second]),
-        None,
-    )
-    .mount(&mock_server)
-    .await;
+    let dest_tree = github_provider
+        .create_destination_tree(instance.clone(), &destination_repository, "main")
+        .await
+        .unwrap();
 
-    let destination_file_root = get_content_json(
-        "repo_one_folder/test1",
-        "test1",
-        Some("dest_my_content"),
-        "file",
-    );
-    let destination_content_folder =
-        get_content_json("repo_one_folder/folder", "folder", None, "dir");
-    let destination_content_file = get_content_json(
-        "repo_one_folder/folder/test2",
-        "test2",
-        Some("dest_my_content_2"),
-        "file",
-    );
-    let destination_content_file_second = get_content_json(
-        "repo_one_folder/folder/test4",
-        "test4",
-        Some("dest_my_content_3"),
-        "file",
-    );
+    let mut events = source_tree.generate_events(&dest_tree);
+    events.sort();
 
-    get_content_mock(
-        &destination_repository.owner,
-        &destination_repository.name,
-        Some("repo_one_folder"),
-        json!([&destination_file_root, &destination_content_folder]),
-        Some("main"),
-    )
-    .mount(&mock_server)
-    .await;
+    assert_eq!(events, get_expected_events());
 
-    get_content_mock(
-        &destination_repository.owner,
-        &destination_repository.name,
-        Some("repo_one_folder/test1"),
-        json!([&destination_file_root]),
-        Some("main"),
-    )
-    .mount(&mock_server)
-    .await;
+    mock_server.verify().await;
+}
 
-    get_content_mock(
-        &destination_repository.owner,
-        &destination_repository.name,
-        Some("repo_one_folder/folder"),
-        json!([&destination_content_file, &destination_content_file_second]),
-        Some("main"),
-    )
-    .mount(&mock_server)
-    .await;
+#[tokio::test]
+async fn generate_events_success_with_config_path() {
+    let destination_repository = DestinationRepository {
+        owner: "owner".to_string(),
+        name: "repo2".to_string(),
+        git_ref: Some("main".to_string()),
+        connection: Default::default(),
+        overrides: Default::default(),
+    };
+    let config = ParsedConfig {
+        version: "0.1".to_string(),
+        source: SourceRepository {
+            owner: "owner".to_string(),
+            name: "repo1".to_string(),
+            git_ref: "main".to_string(),
+            connection: Default::default(),
+        },
+        destinations: vec![destination_repository.clone()],
+        credentials: Credentials::Token(Token::from("random_token")),
+        api_url: None,
+        web_url: None,
+        origin_files: create_workdir_path("folder"),
+        destination_files: create_workdir_path("repo_one_folder"),
+        transformations: Some(vec![Transformation::Move {
+            args: MoveArgs {
+                before: "".to_string(),
+                after: "repo_one_folder".to_string(),
+            },
+            paths: None,
+        }]),
+        update_fns: None,
+        max_concurrency: DEFAULT_MAX_CONCURRENCY,
+        pull_request: Default::default(),
+        job: None,
+    };
 
-    get_content_mock(
-        &destination_repository.owner,
-        &destination_repository.name,
-        Some("repo_one_folder/folder/test2"),
-        json!([&destination_content_file]),
-        Some("main"),
-    )
-    .mount(&mock_server)
-    .await;
-    get_content_mock(
-        &destination_repository.owner,
-        &destination_repository.name,
-        Some("repo_one_folder/folder/test4"),
-        json!([&destination_content_file_second]),
-        Some("main"),
-    )
-    .mount(&mock_server)
-    .await;
+    let mock_server = MockServer::start().await;
+
+    mount_source_mocks(&config, &mock_server).await;
+    mount_destination_mocks(&destination_repository, &mock_server).await;
 
-    let github_provider = GithubProvider { config };
+    let github_provider = GithubProvider::new(config);
 
-    let instance = github_provider.configure_provider(Some(mock_server.uri()));
+    let instance = github_provider
+        .configure_provider(Some(mock_server.uri()))
+        .unwrap();
 
-    let source_tree = github_provider.create_source_tree(instance.clone()).await;
+    let source_tree = github_provider
+        .create_source_tree(instance.clone())
+        .await
+        .unwrap();
 
     let dest_tree = github_provider
-        .create_destination_tree(instance.clone(), &destination_repository)
-        .await;
+        .create_destination_tree(instance.clone(), &destination_repository, "main")
+        .await
+        .unwrap();
 
     let mut events = source_tree.generate_events(&dest_tree);
     events.sort();
 
-    let expected_events = vec![
-        Event::Create {
-            path: "repo_one_folder/folder/test3".to_string(),
-            content: source_content_file_second.decoded_content(),
-        },
-        Event::Update {
-            path: "repo_one_folder/folder/test2".to_string(),
-            content: source_content_file.decoded_content(),
-            sha: destination_content_file.sha,
-        },
-        Event::Delete {
-            path: "repo_one_folder/folder/test4".to_string(),
-            sha: destination_content_file_second.sha,
-        },
-        Event::Delete {
-            path: "repo_one_folder/test1".to_string(),
-            sha: destination_file_root.sha,
-        },
-    ];
-    assert_eq!(events, expected_events);
+    assert_eq!(events, get_expected_events());
 
     mock_server.verify().await;
 }
@@ -0,0 +1,94 @@
+use crate::mocks::github::get_repository_mock;
+use syncy::fixtures::workdir_path::create_workdir_path;
+use syncy::{
+    cli::{
+        Credentials, DestinationRepository, ParsedConfig, SourceRepository, Token,
+        DEFAULT_MAX_CONCURRENCY,
+    },
+    github_provider::GithubProvider,
+    provider::Provider,
+};
+use wiremock::MockServer;
+
+fn get_config(destination: &DestinationRepository) -> ParsedConfig {
+    ParsedConfig {
+        version: "0.1".to_string(),
+        source: SourceRepository {
+            owner: "owner".to_string(),
+            name: "repo1".to_string(),
+            git_ref: "main".to_string(),
+            connection: Default::default(),
+        },
+        destinations: vec![destination.clone()],
+        credentials: Credentials::Token(Token::from("random_token")),
+        api_url: None,
+        web_url: None,
+        destination_files: create_workdir_path(""),
+        origin_files: create_workdir_path(""),
+        transformations: None,
+        update_fns: None,
+        max_concurrency: DEFAULT_MAX_CONCURRENCY,
+        pull_request: Default::default(),
+        job: None,
+    }
+}
+
+#[tokio::test]
+async fn uses_configured_git_ref() {
+    let destination = DestinationRepository {
+        owner: "owner".to_string(),
+        name: "repo2".to_string(),
+        git_ref: Some("develop".to_string()),
+        connection: Default::default(),
+        overrides: Default::default(),
+    };
+
+    let mock_server = MockServer::start().await;
+
+    let github_provider = GithubProvider::new(get_config(&destination));
+
+    let instance = github_provider
+        .configure_provider(Some(mock_server.uri()))
+        .unwrap();
+
+    let base_branch = github_provider
+        .get_destination_base_branch(instance, &destination)
+        .await
+        .unwrap();
+
+    assert_eq!(base_branch, "develop");
+
+    mock_server.verify().await;
+}
+
+#[tokio::test]
+async fn falls_back_to_default_branch() {
+    let destination = DestinationRepository {
+        owner: "owner".to_string(),
+        name: "repo2".to_string(),
+        git_ref: None,
+        connection: Default::default(),
+        overrides: Default::default(),
+    };
+
+    let mock_server = MockServer::start().await;
+
+    get_repository_mock(&destination.owner, &destination.name, "master")
+        .mount(&mock_server)
+        .await;
+
+    let github_provider = GithubProvider::new(get_config(&destination));
+
+    let instance = github_provider
+        .configure_provider(Some(mock_server.uri()))
+        .unwrap();
+
+    let base_branch = github_provider
+        .get_destination_base_branch(instance, &destination)
+        .await
+        .unwrap();
+
+    assert_eq!(base_branch, "master");
+
+    mock_server.verify().await;
+}
@@ -0,0 +1,157 @@
+use crate::mocks::github::{create_installation_token_mock, get_repository_installation_mock};
+use serde_json::json;
+use std::path::PathBuf;
+use syncy::{
+    cli::{Connection, Credentials, GithubApp, Token},
+    github_clients::GithubClients,
+};
+use wiremock::matchers::{header, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+fn get_app_connection(mock_server: &MockServer) -> Connection {
+    Connection {
+        api_url: Some(mock_server.uri()),
+        web_url: mock_server.uri(),
+        credentials: Credentials::App(GithubApp {
+            id: 42,
+            private_key_path: PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+                .join("tests/integration/fixtures/app_private_key.pem"),
+        }),
+    }
+}
+
+/// Only answers requests authenticated with `token`.
+fn get_repository_with_token_mock(owner: &str, repo: &str, token: &str) -> Mock {
+    Mock::given(method("GET"))
+        .and(path(format!("/repos/{}/{}", owner, repo)))
+        .and(header(
+            "Authorization",
+            format!("Bearer {}", token).as_str(),
+        ))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "default_branch": "main",
+        })))
+        .expect(1)
+}
+
+async fn get_default_branch(
+    clients: &GithubClients,
+    connection: &Connection,
+    owner: &str,
+    repo: &str,
+) {
+    let instance = clients.get(connection, owner, repo).await.unwrap();
+
+    let _: serde_json::Value = instance
+        .get(format!("repos/{}/{}", owner, repo), None::<&()>)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn mints_installation_token_per_owner() {
+    let mock_server = MockServer::start().await;
+
+    get_repository_installation_mock("owner", "repo1", 1)
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+    get_repository_installation_mock("other", "repo3", 2)
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+    create_installation_token_mock(1, "ghs_owner", "2999-01-01T00:00:00Z")
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+    create_installation_token_mock(2, "ghs_other", "2999-01-01T00:00:00Z")
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+    get_repository_with_token_mock("owner", "repo2", "ghs_owner")
+        .mount(&mock_server)
+        .await;
+    get_repository_with_token_mock("other", "repo3", "ghs_other")
+        .mount(&mock_server)
+        .await;
+
+    let connection = get_app_connection(&mock_server);
+    let clients = GithubClients::default();
+
+    // the second repository of an owner reuses the installation token
+    clients.get(&connection, "owner", "repo1").await.unwrap();
+    get_default_branch(&clients, &connection, "owner", "repo2").await;
+    get_default_branch(&clients, &connection, "other", "repo3").await;
+
+    mock_server.verify().await;
+}
+
+#[tokio::test]
+async fn refreshes_expiring_installation_token() {
+    let mock_server = MockServer::start().await;
+
+    get_repository_installation_mock("owner", "repo2", 1)
+        .expect(2)
+        .mount(&mock_server)
+        .await;
+    create_installation_token_mock(1, "ghs_owner", "2000-01-01T00:00:00Z")
+        .expect(2)
+        .mount(&mock_server)
+        .await;
+
+    let connection = get_app_connection(&mock_server);
+    let clients = GithubClients::default();
+
+    clients.get(&connection, "owner", "repo2").await.unwrap();
+    clients.get(&connection, "owner", "repo2").await.unwrap();
+
+    mock_server.verify().await;
+}
+
+#[tokio::test]
+async fn missing_installation() {
+    let mock_server = MockServer::start().await;
+
+    let connection = get_app_connection(&mock_server);
+    let clients = GithubClients::default();
+
+    let error = clients
+        .get(&connection, "owner", "repo2")
+        .await
+        .unwrap_err();
+
+    assert!(error.to_string().starts_with(
+        "unexpected provider response: can't find the app installation for owner/repo2"
+    ));
+}
+
+#[tokio::test]
+async fn separate_connections() {
+    let github_server = MockServer::start().await;
+    let enterprise_server = MockServer::start().await;
+
+    get_repository_with_token_mock("owner", "repo1", "github_token")
+        .mount(&github_server)
+        .await;
+    get_repository_with_token_mock("owner", "repo2", "enterprise_token")
+        .mount(&enterprise_server)
+        .await;
+
+    let github = Connection {
+        api_url: Some(github_server.uri()),
+        web_url: github_server.uri(),
+        credentials: Credentials::Token(Token::from("github_token")),
+    };
+    let enterprise = Connection {
+        api_url: Some(enterprise_server.uri()),
+        web_url: enterprise_server.uri(),
+        credentials: Credentials::Token(Token::from("enterprise_token")),
+    };
+    let clients = GithubClients::default();
+
+    get_default_branch(&clients, &github, "owner", "repo1").await;
+    get_default_branch(&clients, &enterprise, "owner", "repo2").await;
+
+    github_server.verify().await;
+    enterprise_server.verify().await;
+}
@@ -1,3 +1,8 @@
+pub mod create_commit;
 pub mod create_source_tree;
+pub mod enterprise_server;
 pub mod generate_events;
+pub mod get_destination_base_branch;
+pub mod github_clients;
 pub mod mocks;
+pub mod pull_request;
@@ -1,35 +1,380 @@
-use std::borrow::Borrow;
-
-use wiremock::matchers::{any, method, path, query_param};
+use serde_json::json;
+use syncy::fixtures::content::get_blob_json;
+use wiremock::matchers::{body_json, body_partial_json, header_exists, method, path, query_param};
 use wiremock::{Mock, ResponseTemplate};
 
-pub fn get_content_mock(
+pub fn get_tree_mock(
     owner: &str,
     repo: &str,
-    pathname: Option<&str>,
-    response: serde_json::Value,
-    r#ref: Option<&str>,
+    git_ref: &str,
+    entries: Vec<serde_json::Value>,
 ) -> Mock {
-    if let Some(val) = r#ref {
-        Mock::given(method("GET"))
-            .and(path(format!(
-                "/repos/{owner}/{repo}/contents/{path}",
-                owner = owner,
-                repo = repo,
-                path = pathname.unwrap_or("")
-            )))
-            .and(query_param("ref", val))
-            .respond_with(ResponseTemplate::new(200).set_body_json(response))
-            .expect(1)
-    } else {
-        Mock::given(method("GET"))
-            .and(path(format!(
-                "/repos/{owner}/{repo}/contents/{path}",
-                owner = owner,
-                repo = repo,
-                path = pathname.unwrap_or("")
-            )))
-            .respond_with(ResponseTemplate::new(200).set_body_json(response))
-            .expect(1)
-    }
+    Mock::given(method("GET"))
+        .and(path(format!(
+            "/repos/{owner}/{repo}/git/trees/{git_ref}",
+            owner = owner,
+            repo = repo,
+            git_ref = git_ref
+        )))
+        .and(query_param("recursive", "1"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "sha": "tree_sha",
+            "tree": entries,
+            "truncated": false,
+        })))
+        .expect(1)
+}
+
+/// A single level of a tree, listed while resolving the folder a work dir starts at.
+pub fn get_tree_level_mock(
+    owner: &str,
+    repo: &str,
+    tree_sha: &str,
+    entries: Vec<serde_json::Value>,
+) -> Mock {
+    Mock::given(method("GET"))
+        .and(path(format!(
+            "/repos/{owner}/{repo}/git/trees/{tree_sha}",
+            owner = owner,
+            repo = repo,
+            tree_sha = tree_sha
+        )))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "sha": tree_sha,
+            "tree": entries,
+            "truncated": false,
+        })))
+        .expect(1)
+}
+
+pub fn get_blob_mock(owner: &str, repo: &str, sha: &str, content: impl AsRef<[u8]>) -> Mock {
+    Mock::given(method("GET"))
+        .and(path(format!(
+            "/repos/{owner}/{repo}/git/blobs/{sha}",
+            owner = owner,
+            repo = repo,
+            sha = sha
+        )))
+        .respond_with(ResponseTemplate::new(200).set_body_json(get_blob_json(sha, content)))
+        .expect(1)
+}
+
+/// A blob whose content didn't make it into the response, e.g. because the
+/// file is too large for the endpoint that returned it.
+pub fn get_empty_blob_mock(owner: &str, repo: &str, sha: &str, size: usize) -> Mock {
+    Mock::given(method("GET"))
+        .and(path(format!(
+            "/repos/{owner}/{repo}/git/blobs/{sha}",
+            owner = owner,
+            repo = repo,
+            sha = sha
+        )))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "sha": sha,
+            "size": size,
+            "content": "",
+            "encoding": "base64",
+        })))
+        .expect(1)
+}
+
+pub fn get_repository_commit_mock(owner: &str, repo: &str, git_ref: &str, sha: &str) -> Mock {
+    Mock::given(method("GET"))
+        .and(path(format!(
+            "/repos/{owner}/{repo}/commits/{git_ref}",
+            owner = owner,
+            repo = repo,
+            git_ref = git_ref
+        )))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "sha": sha })))
+        .expect(1)
+}
+
+pub fn get_ref_mock(owner: &str, repo: &str, branch: &str, sha: &str) -> Mock {
+    Mock::given(method("GET"))
+        .and(path(format!(
+            "/repos/{owner}/{repo}/git/ref/heads/{branch}",
+            owner = owner,
+            repo = repo,
+            branch = branch
+        )))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "ref": format!("refs/heads/{}", branch),
+            "node_id": "",
+            "url": "https://example.net",
+            "object": {
+                "type": "commit",
+                "sha": sha,
+                "url": "https://example.net",
+            },
+        })))
+        .expect(1)
+}
+
+pub fn get_commit_mock(owner: &str, repo: &str, sha: &str, tree_sha: &str) -> Mock {
+    Mock::given(method("GET"))
+        .and(path(format!(
+            "/repos/{owner}/{repo}/git/commits/{sha}",
+            owner = owner,
+            repo = repo,
+            sha = sha
+        )))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "sha": sha,
+            "tree": { "sha": tree_sha },
+        })))
+        .expect(1)
+}
+
+pub fn create_blob_mock(owner: &str, repo: &str, content: impl AsRef<[u8]>, sha: &str) -> Mock {
+    Mock::given(method("POST"))
+        .and(path(format!(
+            "/repos/{owner}/{repo}/git/blobs",
+            owner = owner,
+            repo = repo
+        )))
+        .and(body_json(json!({
+            "content": base64::encode(content),
+            "encoding": "base64",
+        })))
+        .respond_with(ResponseTemplate::new(201).set_body_json(json!({ "sha": sha })))
+        .expect(1)
+}
+
+pub fn create_tree_mock(owner: &str, repo: &str, body: serde_json::Value, sha: &str) -> Mock {
+    Mock::given(method("POST"))
+        .and(path(format!(
+            "/repos/{owner}/{repo}/git/trees",
+            owner = owner,
+            repo = repo
+        )))
+        .and(body_json(body))
+        .respond_with(ResponseTemplate::new(201).set_body_json(json!({ "sha": sha })))
+        .expect(1)
+}
+
+pub fn create_commit_mock(owner: &str, repo: &str, body: serde_json::Value, sha: &str) -> Mock {
+    Mock::given(method("POST"))
+        .and(path(format!(
+            "/repos/{owner}/{repo}/git/commits",
+            owner = owner,
+            repo = repo
+        )))
+        .and(body_json(body))
+        .respond_with(ResponseTemplate::new(201).set_body_json(json!({
+            "sha": sha,
+            "tree": { "sha": "" },
+        })))
+        .expect(1)
+}
+
+pub fn update_ref_mock(owner: &str, repo: &str, branch: &str, sha: &str, force: bool) -> Mock {
+    Mock::given(method("PATCH"))
+        .and(path(format!(
+            "/repos/{owner}/{repo}/git/refs/heads/{branch}",
+            owner = owner,
+            repo = repo,
+            branch = branch
+        )))
+        .and(body_json(json!({ "sha": sha, "force": force })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "ref": format!("refs/heads/{}", branch),
+            "node_id": "",
+            "url": "https://example.net",
+            "object": {
+                "type": "commit",
+                "sha": sha,
+                "url": "https://example.net",
+            },
+        })))
+        .expect(1)
+}
+
+pub fn get_repository_mock(owner: &str, repo: &str, default_branch: &str) -> Mock {
+    Mock::given(method("GET"))
+        .and(path(format!(
+            "/repos/{owner}/{repo}",
+            owner = owner,
+            repo = repo
+        )))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "default_branch": default_branch,
+        })))
+        .expect(1)
+}
+
+pub fn get_matching_refs_mock(owner: &str, repo: &str, branch: &str, refs: Vec<&str>) -> Mock {
+    let response: Vec<serde_json::Value> = refs
+        .iter()
+        .map(|r| {
+            json!({
+                "ref": r,
+                "node_id": "",
+                "url": "https://example.net",
+                "object": {
+                    "type": "commit",
+                    "sha": "",
+                    "url": "https://example.net",
+                },
+            })
+        })
+        .collect();
+
+    Mock::given(method("GET"))
+        .and(path(format!(
+            "/repos/{owner}/{repo}/git/matching-refs/heads/{branch}",
+            owner = owner,
+            repo = repo,
+            branch = branch
+        )))
+        .respond_with(ResponseTemplate::new(200).set_body_json(response))
+        .expect(1)
+}
+
+pub fn create_ref_mock(owner: &str, repo: &str, branch: &str, sha: &str) -> Mock {
+    Mock::given(method("POST"))
+        .and(path(format!(
+            "/repos/{owner}/{repo}/git/refs",
+            owner = owner,
+            repo = repo
+        )))
+        .and(body_json(json!({
+            "ref": format!("refs/heads/{}", branch),
+            "sha": sha,
+        })))
+        .respond_with(ResponseTemplate::new(201).set_body_json(json!({
+            "ref": format!("refs/heads/{}", branch),
+            "node_id": "",
+            "url": "https://example.net",
+            "object": {
+                "type": "commit",
+                "sha": sha,
+                "url": "https://example.net",
+            },
+        })))
+        .expect(1)
+}
+
+pub fn get_open_pull_requests_mock(
+    owner: &str,
+    repo: &str,
+    branch: &str,
+    numbers: Vec<u64>,
+) -> Mock {
+    let response: Vec<serde_json::Value> = numbers
+        .iter()
+        .map(|number| json!({ "number": number }))
+        .collect();
+
+    Mock::given(method("GET"))
+        .and(path(format!(
+            "/repos/{owner}/{repo}/pulls",
+            owner = owner,
+            repo = repo
+        )))
+        .and(query_param("state", "open"))
+        .and(query_param(
+            "head",
+            format!("{owner}:{branch}", owner = owner, branch = branch),
+        ))
+        .respond_with(ResponseTemplate::new(200).set_body_json(response))
+        .expect(1)
+}
+
+pub fn create_pull_request_mock(owner: &str, repo: &str, head: &str, base: &str) -> Mock {
+    Mock::given(method("POST"))
+        .and(path(format!(
+            "/repos/{owner}/{repo}/pulls",
+            owner = owner,
+            repo = repo
+        )))
+        .and(body_partial_json(json!({ "head": head, "base": base })))
+        .respond_with(ResponseTemplate::new(201).set_body_json(json!({ "number": 1 })))
+        .expect(1)
+}
+
+pub fn update_pull_request_mock(owner: &str, repo: &str, number: u64) -> Mock {
+    Mock::given(method("PATCH"))
+        .and(path(format!(
+            "/repos/{owner}/{repo}/pulls/{number}",
+            owner = owner,
+            repo = repo,
+            number = number
+        )))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "number": number })))
+        .expect(1)
+}
+
+pub fn close_pull_request_mock(owner: &str, repo: &str, number: u64) -> Mock {
+    Mock::given(method("PATCH"))
+        .and(path(format!(
+            "/repos/{owner}/{repo}/pulls/{number}",
+            owner = owner,
+            repo = repo,
+            number = number
+        )))
+        .and(body_json(json!({ "state": "closed" })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "number": number })))
+        .expect(1)
+}
+
+pub fn add_labels_mock(owner: &str, repo: &str, number: u64, labels: &[&str]) -> Mock {
+    Mock::given(method("POST"))
+        .and(path(format!(
+            "/repos/{owner}/{repo}/issues/{number}/labels",
+            owner = owner,
+            repo = repo,
+            number = number
+        )))
+        .and(body_partial_json(json!({ "labels": labels })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+        .expect(1)
+}
+
+pub fn update_ref_conflict_mock(owner: &str, repo: &str, branch: &str) -> Mock {
+    Mock::given(method("PATCH"))
+        .and(path(format!(
+            "/repos/{owner}/{repo}/git/refs/heads/{branch}",
+            owner = owner,
+            repo = repo,
+            branch = branch
+        )))
+        .respond_with(ResponseTemplate::new(422).set_body_json(json!({
+            "message": "Update is not a fast forward",
+        })))
+        .expect(1)
+}
+
+pub fn not_found_mock(http_method: &str, pathname: &str) -> Mock {
+    Mock::given(method(http_method))
+        .and(path(pathname))
+        .respond_with(ResponseTemplate::new(404).set_body_json(json!({
+            "message": "Not Found",
+        })))
+        .expect(1)
+}
+
+pub fn get_repository_installation_mock(owner: &str, repo: &str, installation_id: u64) -> Mock {
+    Mock::given(method("GET"))
+        .and(path(format!(
+            "/repos/{owner}/{repo}/installation",
+            owner = owner,
+            repo = repo
+        )))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "id": installation_id,
+        })))
+}
+
+pub fn create_installation_token_mock(installation_id: u64, token: &str, expires_at: &str) -> Mock {
+    Mock::given(method("POST"))
+        .and(path(format!(
+            "/app/installations/{installation_id}/access_tokens",
+            installation_id = installation_id
+        )))
+        .and(header_exists("Authorization"))
+        .respond_with(ResponseTemplate::new(201).set_body_json(json!({
+            "token": token,
+            "expires_at": expires_at,
+        })))
 }
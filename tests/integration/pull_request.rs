@@ -0,0 +1,404 @@
+use crate::mocks::github::{
+    add_labels_mock, close_pull_request_mock, create_pull_request_mock, create_ref_mock,
+    get_commit_mock, get_matching_refs_mock, get_open_pull_requests_mock, get_ref_mock,
+    update_pull_request_mock, update_ref_mock,
+};
+use serde_json::json;
+use syncy::fixtures::workdir_path::create_workdir_path;
+use syncy::{
+    cli::{
+        Credentials, DestinationRepository, ParsedConfig, PullRequestConfig, SourceRepository,
+        Token, DEFAULT_MAX_CONCURRENCY,
+    },
+    github_provider::GithubProvider,
+    provider::Provider,
+};
+use wiremock::{
+    matchers::{body_partial_json, body_string_contains, method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+
+fn get_provider(destination: &DestinationRepository) -> GithubProvider {
+    let config = ParsedConfig {
+        version: "0.1".to_string(),
+        source: SourceRepository {
+            owner: "owner".to_string(),
+            name: "repo1".to_string(),
+            git_ref: "main".to_string(),
+            connection: Default::default(),
+        },
+        destinations: vec![destination.clone()],
+        credentials: Credentials::Token(Token::from("random_token")),
+        api_url: None,
+        web_url: None,
+        destination_files: create_workdir_path(""),
+        origin_files: create_workdir_path(""),
+        transformations: None,
+        update_fns: None,
+        max_concurrency: DEFAULT_MAX_CONCURRENCY,
+        pull_request: Default::default(),
+        job: None,
+    };
+
+    GithubProvider::new(config)
+}
+
+fn get_destination() -> DestinationRepository {
+    DestinationRepository {
+        owner: "owner".to_string(),
+        name: "repo2".to_string(),
+        git_ref: Some("main".to_string()),
+        connection: Default::default(),
+        overrides: Default::default(),
+    }
+}
+
+#[test]
+fn destination_branch_is_stable() {
+    let github_provider = get_provider(&get_destination());
+
+    assert_eq!(
+        github_provider.get_destination_branch(),
+        "syncy/owner/repo1/main"
+    );
+    assert_eq!(
+        github_provider.get_destination_branch(),
+        github_provider.get_destination_branch()
+    );
+}
+
+#[test]
+fn destination_branch_includes_the_job() {
+    let mut github_provider = get_provider(&get_destination());
+    github_provider.config.job = Some("docs".to_string());
+
+    assert_eq!(
+        github_provider.get_destination_branch(),
+        "syncy/docs/owner/repo1/main"
+    );
+}
+
+#[tokio::test]
+async fn creates_missing_destination_branch() {
+    let destination = get_destination();
+    let branch = "syncy/owner/repo1/main";
+
+    let mock_server = MockServer::start().await;
+
+    get_ref_mock(&destination.owner, &destination.name, "main", "base_sha")
+        .mount(&mock_server)
+        .await;
+    get_matching_refs_mock(
+        &destination.owner,
+        &destination.name,
+        branch,
+        vec!["refs/heads/syncy/owner/repo1/main-other"],
+    )
+    .mount(&mock_server)
+    .await;
+    create_ref_mock(&destination.owner, &destination.name, branch, "base_sha")
+        .mount(&mock_server)
+        .await;
+
+    let github_provider = get_provider(&destination);
+    let instance = github_provider
+        .configure_provider(Some(mock_server.uri()))
+        .unwrap();
+
+    let updated = github_provider
+        .create_destination_branch(instance, &destination, "main", branch, "tree_sha")
+        .await
+        .unwrap();
+
+    assert!(updated);
+    mock_server.verify().await;
+}
+
+#[tokio::test]
+async fn force_updates_existing_destination_branch() {
+    let destination = get_destination();
+    let branch = "syncy/owner/repo1/main";
+
+    let mock_server = MockServer::start().await;
+
+    get_ref_mock(&destination.owner, &destination.name, "main", "base_sha")
+        .mount(&mock_server)
+        .await;
+    get_matching_refs_mock(
+        &destination.owner,
+        &destination.name,
+        branch,
+        vec!["refs/heads/syncy/owner/repo1/main"],
+    )
+    .mount(&mock_server)
+    .await;
+    get_ref_mock(&destination.owner, &destination.name, branch, "head_sha")
+        .mount(&mock_server)
+        .await;
+    get_commit_mock(
+        &destination.owner,
+        &destination.name,
+        "head_sha",
+        "old_tree_sha",
+    )
+    .mount(&mock_server)
+    .await;
+    update_ref_mock(
+        &destination.owner,
+        &destination.name,
+        branch,
+        "base_sha",
+        true,
+    )
+    .mount(&mock_server)
+    .await;
+
+    let github_provider = get_provider(&destination);
+    let instance = github_provider
+        .configure_provider(Some(mock_server.uri()))
+        .unwrap();
+
+    let updated = github_provider
+        .create_destination_branch(instance, &destination, "main", branch, "tree_sha")
+        .await
+        .unwrap();
+
+    assert!(updated);
+    mock_server.verify().await;
+}
+
+#[tokio::test]
+async fn keeps_destination_branch_with_the_same_tree() {
+    let destination = get_destination();
+    let branch = "syncy/owner/repo1/main";
+
+    let mock_server = MockServer::start().await;
+
+    get_matching_refs_mock(
+        &destination.owner,
+        &destination.name,
+        branch,
+        vec!["refs/heads/syncy/owner/repo1/main"],
+    )
+    .mount(&mock_server)
+    .await;
+    get_ref_mock(&destination.owner, &destination.name, branch, "head_sha")
+        .mount(&mock_server)
+        .await;
+    get_commit_mock(
+        &destination.owner,
+        &destination.name,
+        "head_sha",
+        "tree_sha",
+    )
+    .mount(&mock_server)
+    .await;
+
+    let github_provider = get_provider(&destination);
+    let instance = github_provider
+        .configure_provider(Some(mock_server.uri()))
+        .unwrap();
+
+    // neither reset nor committed, so reviewers of the open pull request aren't notified
+    let updated = github_provider
+        .create_destination_branch(instance, &destination, "main", branch, "tree_sha")
+        .await
+        .unwrap();
+
+    assert!(!updated);
+    mock_server.verify().await;
+}
+
+#[tokio::test]
+async fn creates_pull_request_when_none_is_open() {
+    let destination = get_destination();
+    let branch = "syncy/owner/repo1/main";
+
+    let mock_server = MockServer::start().await;
+
+    get_open_pull_requests_mock(&destination.owner, &destination.name, branch, vec![])
+        .mount(&mock_server)
+        .await;
+    create_pull_request_mock(&destination.owner, &destination.name, branch, "main")
+        .mount(&mock_server)
+        .await;
+
+    let github_provider = get_provider(&destination);
+    let instance = github_provider
+        .configure_provider(Some(mock_server.uri()))
+        .unwrap();
+
+    github_provider
+        .create_pull_request_destination(instance, &destination, "main", branch)
+        .await
+        .unwrap();
+
+    mock_server.verify().await;
+}
+
+#[tokio::test]
+async fn updates_open_pull_request() {
+    let destination = get_destination();
+    let branch = "syncy/owner/repo1/main";
+
+    let mock_server = MockServer::start().await;
+
+    get_open_pull_requests_mock(&destination.owner, &destination.name, branch, vec![42])
+        .mount(&mock_server)
+        .await;
+    update_pull_request_mock(&destination.owner, &destination.name, 42)
+        .mount(&mock_server)
+        .await;
+
+    let github_provider = get_provider(&destination);
+    let instance = github_provider
+        .configure_provider(Some(mock_server.uri()))
+        .unwrap();
+
+    github_provider
+        .create_pull_request_destination(instance, &destination, "main", branch)
+        .await
+        .unwrap();
+
+    mock_server.verify().await;
+}
+
+#[tokio::test]
+async fn links_source_on_its_web_url() {
+    let destination = get_destination();
+    let branch = "syncy/owner/repo1/main";
+
+    let mock_server = MockServer::start().await;
+
+    get_open_pull_requests_mock(&destination.owner, &destination.name, branch, vec![])
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/repos/owner/repo2/pulls"))
+        .and(body_string_contains(
+            "link to the original repo: https://github.example.com/owner/repo1/main",
+        ))
+        .respond_with(ResponseTemplate::new(201).set_body_json(json!({ "number": 1 })))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    let mut github_provider = get_provider(&destination);
+    github_provider.config.source.connection.api_url =
+        Some("https://github.example.com/api/v3/".to_string());
+    let instance = github_provider
+        .configure_provider(Some(mock_server.uri()))
+        .unwrap();
+
+    github_provider
+        .create_pull_request_destination(instance, &destination, "main", branch)
+        .await
+        .unwrap();
+
+    mock_server.verify().await;
+}
+
+#[tokio::test]
+async fn applies_destination_pull_request_settings() {
+    let mut destination = get_destination();
+    destination.overrides.pull_request = PullRequestConfig {
+        title: None,
+        draft: Some(true),
+        labels: Some(vec!["vendored".to_string()]),
+    };
+    let branch = "syncy/owner/repo1/main";
+
+    let mock_server = MockServer::start().await;
+
+    get_open_pull_requests_mock(&destination.owner, &destination.name, branch, vec![])
+        .mount(&mock_server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/repos/owner/repo2/pulls"))
+        .and(body_partial_json(
+            json!({ "title": "Sync shared code", "draft": true }),
+        ))
+        .respond_with(ResponseTemplate::new(201).set_body_json(json!({ "number": 7 })))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+    add_labels_mock(&destination.owner, &destination.name, 7, &["vendored"])
+        .mount(&mock_server)
+        .await;
+
+    let mut github_provider = get_provider(&destination);
+    github_provider.config.pull_request = PullRequestConfig {
+        title: Some("Sync shared code".to_string()),
+        draft: None,
+        labels: Some(vec!["sync".to_string()]),
+    };
+    let destination_provider = GithubProvider::new(
+        github_provider
+            .config
+            .for_destination(&destination)
+            .unwrap(),
+    );
+    let instance = destination_provider
+        .configure_provider(Some(mock_server.uri()))
+        .unwrap();
+
+    destination_provider
+        .create_pull_request_destination(instance, &destination, "main", branch)
+        .await
+        .unwrap();
+
+    mock_server.verify().await;
+}
+
+#[tokio::test]
+async fn closes_stale_pull_request() {
+    let destination = get_destination();
+    let branch = "syncy/owner/repo1/main";
+
+    let mock_server = MockServer::start().await;
+
+    get_open_pull_requests_mock(&destination.owner, &destination.name, branch, vec![42])
+        .mount(&mock_server)
+        .await;
+    close_pull_request_mock(&destination.owner, &destination.name, 42)
+        .mount(&mock_server)
+        .await;
+
+    let github_provider = get_provider(&destination);
+    let instance = github_provider
+        .configure_provider(Some(mock_server.uri()))
+        .unwrap();
+
+    let closed = github_provider
+        .close_pull_request_destination(instance, &destination, branch)
+        .await
+        .unwrap();
+
+    assert_eq!(closed, Some(42));
+    mock_server.verify().await;
+}
+
+#[tokio::test]
+async fn closes_nothing_when_no_pull_request_is_open() {
+    let destination = get_destination();
+    let branch = "syncy/owner/repo1/main";
+
+    let mock_server = MockServer::start().await;
+
+    get_open_pull_requests_mock(&destination.owner, &destination.name, branch, vec![])
+        .mount(&mock_server)
+        .await;
+
+    let github_provider = get_provider(&destination);
+    let instance = github_provider
+        .configure_provider(Some(mock_server.uri()))
+        .unwrap();
+
+    let closed = github_provider
+        .close_pull_request_destination(instance, &destination, branch)
+        .await
+        .unwrap();
+
+    assert_eq!(closed, None);
+    mock_server.verify().await;
+}